@@ -0,0 +1,90 @@
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+/// An axis-aligned bounding box
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn new(min: Point3, max: Point3) -> Self {
+        Self { min, max }
+    }
+
+    /// A box that contains nothing, surrounding it with another box gives that box
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    /// The smallest box containing both boxes
+    pub fn surrounding(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.min(&other.min),
+            max: self.max.max(&other.max),
+        }
+    }
+
+    /// Grow the box so it contains the point
+    pub fn include(&self, p: &Point3) -> Self {
+        Self {
+            min: self.min.min(p),
+            max: self.max.max(p),
+        }
+    }
+
+    /// The center of the box
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    /// The area of the surface of the box, zero for an empty box
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        if d.x < 0. || d.y < 0. || d.z < 0. {
+            return 0.;
+        }
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// The axis along which the box is largest, 0 for x, 1 for y and 2 for z
+    pub fn longest_axis(&self) -> usize {
+        let d = self.max - self.min;
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Check if the ray passes through the box between t_min and t_max
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    /// The part of [t_min, t_max] in which the ray is inside the box
+    pub fn intersect(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        // Intersect the ray with the slabs between the planes of each axis
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.dir[axis];
+            let mut t0 = (self.min[axis] - r.origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - r.origin[axis]) * inv_d;
+            if inv_d < 0. {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // Written so that a NaN from a ray in the plane of the slab does not count as a miss
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
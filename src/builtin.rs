@@ -0,0 +1,162 @@
+use crate::bvh;
+use crate::camera::CameraBuilder;
+use crate::color::Color;
+use crate::environment::{GradientEnvironment, SolidEnvironment};
+use crate::geometry::{Cuboid, Sphere, XyRect, XzRect, YzRect};
+use crate::hittable::Hittable;
+use crate::material::{Dialectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::matrix::Mat4;
+use crate::render::RenderSettings;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::transform::Transformed;
+use crate::vec3::{Point3, Vec3};
+use rand::Rng;
+use std::sync::Arc;
+
+/// Names of all built-in scenes
+pub const BUILTIN_SCENES: &[&str] = &["final", "cornell_box"];
+
+/// Create the built-in scene with the given name
+pub fn builtin_scene(name: &str, seed: u64) -> Option<Scene> {
+    match name {
+        "final" => Some(final_scene(seed)),
+        "cornell_box" => Some(cornell_box(seed)),
+        _ => None,
+    }
+}
+
+/// The scene from the cover of "Ray Tracing in One Weekend"
+///
+/// A grid of small spheres with random materials surrounds three large spheres.
+/// The same seed always generates the same scene
+pub fn final_scene(seed: u64) -> Scene {
+    let mut sampler = Sampler::from_seed(seed);
+    let mut world: Vec<Box<dyn Hittable>> = Vec::new();
+    // Material on the ground
+    let material_ground = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    // Set the ground plane
+    world.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_material: f64 = sampler.gen();
+            let center = Point3::new(
+                a as f64 + 0.9 * sampler.gen::<f64>(),
+                0.2,
+                b as f64 + 0.9 * sampler.gen::<f64>(),
+            );
+            // Keep the space around the big metal sphere free
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() <= 0.9 {
+                continue;
+            }
+
+            let material: Arc<dyn Material> = if choose_material < 0.8 {
+                let albedo = Color::random(&mut sampler) * Color::random(&mut sampler);
+                Arc::new(Lambertian::new(albedo))
+            } else if choose_material < 0.95 {
+                let albedo = Color::random_range(0.5, 1.0, &mut sampler);
+                let fuzz = sampler.gen_range(0.0, 0.5);
+                Arc::new(Metal::new(albedo, fuzz))
+            } else {
+                Arc::new(Dialectric::new(1.5))
+            };
+            world.push(Box::new(Sphere::new(center, 0.2, material)));
+        }
+    }
+
+    world.push(Box::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Dialectric::new(1.5)),
+    )));
+    world.push(Box::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1))),
+    )));
+    world.push(Box::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0)),
+    )));
+
+    let mut camera = CameraBuilder::new(Point3::new(13.0, 2.0, 3.0), Point3::zero());
+    camera.set_up_vector(&Vec3::new(0.0, 1.0, 0.0));
+    camera.set_aperture(0.1);
+    camera.set_focus_distance(10.0);
+
+    Scene {
+        objects: bvh::accelerate(world),
+        settings: RenderSettings {
+            width: 1200,
+            height: 800,
+            samples_per_pixel: 500,
+            max_depth: 50,
+            seed,
+            ..RenderSettings::default()
+        },
+        camera,
+        environment: Box::new(GradientEnvironment::default()),
+    }
+}
+
+/// The Cornell box, lit only by a panel in the ceiling
+///
+/// Red and green side walls surround two white boxes. The box is open towards the
+/// camera, but the environment is black
+pub fn cornell_box(seed: u64) -> Scene {
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+    let mut world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green)),
+        Box::new(YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red)),
+        Box::new(XzRect::new(213.0, 343.0, 227.0, 332.0, 554.0, light)),
+        Box::new(XzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone())),
+        Box::new(XzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone())),
+        Box::new(XyRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone())),
+    ];
+    let mut add_box = |size: Vec3, angle: f64, offset: Vec3| {
+        let cuboid = Cuboid::new(Point3::zero(), size, white.clone());
+        let transform = Mat4::translation(&offset) * Mat4::rotation_y(angle);
+        world.push(Box::new(Transformed::new(cuboid, transform)));
+    };
+    add_box(
+        Vec3::new(165.0, 330.0, 165.0),
+        15.0,
+        Vec3::new(265.0, 0.0, 295.0),
+    );
+    add_box(
+        Vec3::new(165.0, 165.0, 165.0),
+        -18.0,
+        Vec3::new(130.0, 0.0, 65.0),
+    );
+
+    let mut camera = CameraBuilder::new(
+        Point3::new(278.0, 278.0, -800.0),
+        Point3::new(278.0, 278.0, 0.0),
+    );
+    camera.set_up_vector(&Vec3::new(0.0, 1.0, 0.0));
+    camera.set_vfov(40.0);
+    camera.set_aperture(0.0);
+
+    Scene {
+        objects: bvh::accelerate(world),
+        settings: RenderSettings {
+            width: 600,
+            height: 600,
+            samples_per_pixel: 200,
+            max_depth: 50,
+            seed,
+            ..RenderSettings::default()
+        },
+        camera,
+        environment: Box::new(SolidEnvironment::new(Color::zero())),
+    }
+}
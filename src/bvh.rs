@@ -0,0 +1,280 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+
+/// Number of buckets the centroids are sorted into when looking for a split
+const SAH_BUCKETS: usize = 12;
+/// Cost of testing a ray against a bounding box relative to testing an object
+const TRAVERSAL_COST: f64 = 0.5;
+/// Nodes with at most this many objects always become a leaf
+const MAX_LEAF_SIZE: usize = 4;
+
+/// A bounding volume hierarchy node
+///
+/// The objects are split using the surface area heuristic (SAH), which divides them
+/// so that the expected cost of tracing a ray through the tree is as low as possible
+pub struct BvhNode<H = Box<dyn Hittable>> {
+    bbox: Aabb,
+    content: BvhContent<H>,
+}
+
+enum BvhContent<H> {
+    Leaf(Vec<H>),
+    Branch(Box<BvhNode<H>>, Box<BvhNode<H>>),
+}
+
+/// An object together with its bounding box, used while building the tree
+struct Primitive<H> {
+    object: H,
+    bbox: Aabb,
+    centroid: f64,
+}
+
+impl<H: Hittable> BvhNode<H> {
+    /// Build a tree containing all objects
+    ///
+    /// Panics when one of the objects is unbounded, use [`accelerate`] to build a world
+    /// that contains unbounded objects
+    pub fn new(objects: Vec<H>) -> Self {
+        Self::with_bounds(objects.into_iter().map(|object| {
+            let bbox = object
+                .bounding_box()
+                .expect("objects in a BVH need a bounding box");
+            (object, bbox)
+        }))
+    }
+}
+
+impl<H> BvhNode<H> {
+    /// Build a tree of objects that are not hittable by themselves, like indices into
+    /// shared data, from their bounding boxes
+    pub(crate) fn with_bounds(objects: impl IntoIterator<Item = (H, Aabb)>) -> Self {
+        let primitives = objects
+            .into_iter()
+            .map(|(object, bbox)| Primitive {
+                object,
+                bbox,
+                centroid: 0.,
+            })
+            .collect();
+        Self::build(primitives)
+    }
+
+    fn build(mut primitives: Vec<Primitive<H>>) -> Self {
+        let bbox = primitives
+            .iter()
+            .fold(Aabb::empty(), |bbox, p| bbox.surrounding(&p.bbox));
+        let leaf = |primitives: Vec<Primitive<H>>| BvhNode {
+            bbox,
+            content: BvhContent::Leaf(primitives.into_iter().map(|p| p.object).collect()),
+        };
+        if primitives.len() <= 1 {
+            return leaf(primitives);
+        }
+
+        // Split along the axis where the centroids are spread out the most
+        let centroid_bounds = primitives
+            .iter()
+            .fold(Aabb::empty(), |b, p| b.include(&p.bbox.centroid()));
+        let axis = centroid_bounds.longest_axis();
+        let (min, max) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+        for primitive in primitives.iter_mut() {
+            primitive.centroid = primitive.bbox.centroid()[axis];
+        }
+        if max <= min {
+            // All centroids are in the same place, there is nothing to split
+            return leaf(primitives);
+        }
+
+        // Sort the objects into buckets along the axis
+        let bucket_of = |centroid: f64| {
+            (((centroid - min) / (max - min) * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+        };
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut bounds = [Aabb::empty(); SAH_BUCKETS];
+        for primitive in primitives.iter() {
+            let bucket = bucket_of(primitive.centroid);
+            counts[bucket] += 1;
+            bounds[bucket] = bounds[bucket].surrounding(&primitive.bbox);
+        }
+
+        // Find the split after bucket i with the lowest expected cost
+        let mut best_split = 0;
+        let mut best_cost = f64::INFINITY;
+        for split in 0..SAH_BUCKETS - 1 {
+            let (mut left, mut right) = (Aabb::empty(), Aabb::empty());
+            let (mut left_count, mut right_count) = (0, 0);
+            for bucket in 0..=split {
+                left = left.surrounding(&bounds[bucket]);
+                left_count += counts[bucket];
+            }
+            for bucket in split + 1..SAH_BUCKETS {
+                right = right.surrounding(&bounds[bucket]);
+                right_count += counts[bucket];
+            }
+            let cost =
+                left_count as f64 * left.surface_area() + right_count as f64 * right.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        // Only split when that is expected to be cheaper than testing every object
+        let split_cost = TRAVERSAL_COST + best_cost / bbox.surface_area();
+        let leaf_cost = primitives.len() as f64;
+        if primitives.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+            return leaf(primitives);
+        }
+
+        let (left, right): (Vec<_>, Vec<_>) = primitives
+            .into_iter()
+            .partition(|p| bucket_of(p.centroid) <= best_split);
+        BvhNode {
+            bbox,
+            content: BvhContent::Branch(
+                Box::new(BvhNode::build(left)),
+                Box::new(BvhNode::build(right)),
+            ),
+        }
+    }
+}
+
+impl<H> BvhNode<H> {
+    /// Box around all objects in the tree
+    pub(crate) fn bounds(&self) -> Aabb {
+        self.bbox
+    }
+
+    /// The closest hit of a ray, where `hit` tests the ray against a single object
+    pub(crate) fn hit_with<'a, F>(
+        &'a self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        hit: &F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: Fn(&'a H, f64, f64) -> Option<HitRecord<'a>>,
+    {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+        match &self.content {
+            BvhContent::Leaf(objects) => {
+                let mut closest_so_far = t_max;
+                let mut hit_record = None;
+                for object in objects {
+                    if let Some(record) = hit(object, t_min, closest_so_far) {
+                        closest_so_far = record.t;
+                        hit_record = Some(record);
+                    }
+                }
+                hit_record
+            }
+            BvhContent::Branch(left, right) => {
+                let left_hit = left.hit_with(r, t_min, t_max, hit);
+                let closest_so_far = left_hit.as_ref().map(|hit| hit.t).unwrap_or(t_max);
+                right.hit_with(r, t_min, closest_so_far, hit).or(left_hit)
+            }
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for BvhNode<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.hit_with(r, t_min, t_max, &|object: &H, t_min, t_max| {
+            object.hit(r, t_min, t_max)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+/// Put all bounded objects into a BVH, unbounded objects are kept as they are
+pub fn accelerate(objects: Vec<Box<dyn Hittable>>) -> Vec<Box<dyn Hittable>> {
+    let (bounded, mut world): (Vec<_>, Vec<_>) = objects
+        .into_iter()
+        .partition(|object| object.bounding_box().is_some());
+    if !bounded.is_empty() {
+        world.push(Box::new(BvhNode::new(bounded)));
+    }
+    world
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Sphere;
+    use crate::material::tests::gray;
+    use crate::sampler::Sampler;
+    use crate::vec3::Vec3;
+    use rand::Rng;
+
+    fn random_spheres(count: usize, sampler: &mut Sampler) -> Vec<Box<dyn Hittable>> {
+        let material = gray();
+        (0..count)
+            .map(|_| {
+                let center = Vec3::random_range(-10., 10., sampler);
+                let radius = sampler.gen_range(0.05, 1.0);
+                Box::new(Sphere::new(center, radius, material.clone())) as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hits_match_linear_list() {
+        let mut sampler = Sampler::from_seed(42);
+        let bvh = BvhNode::new(random_spheres(500, &mut sampler));
+        let mut sampler = Sampler::from_seed(42);
+        let spheres = random_spheres(500, &mut sampler);
+        let list: Vec<&dyn Hittable> = spheres.iter().map(|s| s.as_ref()).collect();
+
+        let mut hits = 0;
+        for _ in 0..10_000 {
+            let origin = Vec3::random_range(-15., 15., &mut sampler);
+            let dir = Vec3::random_range(-1., 1., &mut sampler);
+            let ray = Ray::new(origin, dir);
+            let expected = list.hit(&ray, 0.001, f64::INFINITY);
+            let actual = bvh.hit(&ray, 0.001, f64::INFINITY);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(expected), Some(actual)) => {
+                    hits += 1;
+                    assert_eq!(expected.t, actual.t);
+                    assert_eq!(expected.front_face, actual.front_face);
+                    assert_eq!((expected.p - actual.p).length(), 0.);
+                    assert_eq!((expected.normal - actual.normal).length(), 0.);
+                }
+                (expected, actual) => panic!(
+                    "linear list hit: {}, bvh hit: {}",
+                    expected.is_some(),
+                    actual.is_some()
+                ),
+            }
+        }
+        assert!(hits > 1000, "too few rays hit anything to be a useful test");
+    }
+
+    #[test]
+    fn bounding_box_contains_all_objects() {
+        let mut sampler = Sampler::from_seed(7);
+        let spheres = random_spheres(100, &mut sampler);
+        let expected = spheres.iter().fold(Aabb::empty(), |b, s| {
+            b.surrounding(&s.bounding_box().unwrap())
+        });
+        let bbox = BvhNode::new(spheres).bounding_box().unwrap();
+        assert_eq!((bbox.min - expected.min).length(), 0.);
+        assert_eq!((bbox.max - expected.max).length(), 0.);
+    }
+
+    #[test]
+    fn accelerate_groups_bounded_objects() {
+        let mut sampler = Sampler::from_seed(3);
+        let world = accelerate(random_spheres(10, &mut sampler));
+        assert_eq!(world.len(), 1);
+        assert!(accelerate(Vec::new()).is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     ray::Ray,
+    sampler::Sampler,
     vec3::{Point3, Vec3},
 };
 
@@ -11,11 +12,11 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
     lens_radius: f64,
 }
 
 /// Struct to create a camera with specific settings
+#[derive(Clone)]
 pub struct CameraBuilder {
     // Look from this point
     look_from: Point3,
@@ -53,6 +54,12 @@ impl CameraBuilder {
         self
     }
 
+    /// Set the vertical field of view in degrees
+    pub fn set_vfov(&mut self, vfov_degrees: f64) -> &Self {
+        self.vfov_degrees = vfov_degrees;
+        self
+    }
+
     /// Set the aperture of the camera lens
     pub fn set_aperture(&mut self, aperture: f64) -> &Self {
         self.aperture = aperture;
@@ -126,18 +133,17 @@ impl Camera {
             vertical,
             u,
             v,
-            w,
             lens_radius: aperture / 2.,
         }
     }
 
     /// Cast a ray with the camera
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+    pub fn get_ray(&self, s: f64, t: f64, sampler: &mut Sampler) -> Ray {
         // Get an offset for the lens radius
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+        let rd = self.lens_radius * Vec3::random_in_unit_disk(sampler);
         // Transform it into the correct frame
         let offset = (self.u * rd.x) + (self.v * rd.y);
-        
+
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
@@ -1,4 +1,5 @@
 use crate::vec3::Vec3;
+use std::str::FromStr;
 
 pub type Color = Vec3;
 
@@ -12,19 +13,64 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     }
 }
 
-/// Write a color to the standard out
-pub fn write_color(c: &Color, samples_per_pixel: u32) {
-    let scale = 1.0 / samples_per_pixel as f64;
-    // Divide the color by the number of samples
-    let r = (c.x * scale).sqrt();
-    let g = (c.y * scale).sqrt();
-    let b = (c.z * scale).sqrt();
-
-    // Write the translated [0,255] value of each color component
-    println!(
-        "{:.0} {:.0} {:.0}",
-        256.0 * clamp(r, 0., 0.999),
-        256.0 * clamp(g, 0., 0.999),
-        256.0 * clamp(b, 0., 0.999),
-    );
+/// Convert an sRGB encoded value in the [0, 1] range to a linear value
+pub fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Brightness of a linear color as perceived by the eye, using the Rec. 709 weights
+pub fn luminance(c: &Color) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+/// Operator that maps linear HDR colors into the displayable [0, 1] range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Cut off everything brighter than 1
+    Clamp,
+    /// Reinhard's operator c / (1 + c), which compresses bright colors
+    Reinhard,
+}
+
+impl ToneMap {
+    /// Apply the tone mapping to a linear color
+    pub fn apply(&self, c: &Color) -> Color {
+        match self {
+            ToneMap::Clamp => {
+                Color::new(clamp(c.x, 0., 1.), clamp(c.y, 0., 1.), clamp(c.z, 0., 1.))
+            }
+            ToneMap::Reinhard => {
+                let map = |x: f64| {
+                    let x = x.max(0.);
+                    x / (1. + x)
+                };
+                Color::new(map(c.x), map(c.y), map(c.z))
+            }
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            _ => Err(format!(
+                "unknown tone mapping `{}`, expected `clamp` or `reinhard`",
+                s
+            )),
+        }
+    }
+}
+
+/// Apply gamma correction to a color in the [0, 1] range
+pub fn gamma_correct(c: &Color, gamma: f64) -> Color {
+    let exponent = 1.0 / gamma;
+    Color::new(c.x.powf(exponent), c.y.powf(exponent), c.z.powf(exponent))
 }
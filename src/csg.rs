@@ -0,0 +1,184 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, Solid};
+use crate::ray::Ray;
+
+/// How the two solids of a [`Csg`] are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    /// Everything inside either solid
+    Union,
+    /// Everything inside both solids
+    Intersection,
+    /// Everything inside the left solid but outside the right one
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Two solids combined with constructive solid geometry
+///
+/// The hits of both solids along a ray are walked in order, keeping track of whether
+/// the ray is inside each of them. Only the hits where the ray enters or leaves the
+/// combined solid are kept
+pub struct Csg {
+    operation: CsgOperation,
+    left: Box<dyn Solid>,
+    right: Box<dyn Solid>,
+    bbox: Option<Aabb>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        let bbox = match (operation, left.bounding_box(), right.bounding_box()) {
+            (CsgOperation::Union, Some(a), Some(b)) => Some(a.surrounding(&b)),
+            (CsgOperation::Union, _, _) => None,
+            (CsgOperation::Intersection, Some(a), Some(b)) => {
+                Some(Aabb::new(a.min.max(&b.min), a.max.min(&b.max)))
+            }
+            (CsgOperation::Intersection, a, b) => a.or(b),
+            (CsgOperation::Difference, a, _) => a,
+        };
+        Self {
+            operation,
+            left,
+            right,
+            bbox,
+        }
+    }
+
+    pub fn union(left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        Self::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+
+    /// The left solid with the right solid cut out of it
+    pub fn difference(left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if let Some(bbox) = &self.bbox {
+            if !bbox.hit(r, t_min, t_max) {
+                return None;
+            }
+        }
+        self.intersections(r)
+            .into_iter()
+            .find(|hit| hit.t > t_min && hit.t < t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
+
+impl Solid for Csg {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        let mut left = self.left.intersections(r).into_iter().peekable();
+        let mut right = self.right.intersections(r).into_iter().peekable();
+        // The line of the ray starts outside of both solids
+        let (mut in_left, mut in_right) = (false, false);
+        let mut hits = Vec::new();
+        loop {
+            let from_left = match (left.peek(), right.peek()) {
+                (Some(a), Some(b)) => a.t <= b.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let was_inside = self.operation.contains(in_left, in_right);
+            let mut hit = if from_left {
+                let hit = left.next().unwrap();
+                in_left = hit.front_face;
+                hit
+            } else {
+                let hit = right.next().unwrap();
+                in_right = hit.front_face;
+                hit
+            };
+            if self.operation.contains(in_left, in_right) == was_inside {
+                continue;
+            }
+            // The outside of a cut out solid is the inside of the result, so its surface
+            // faces the other way. The normal stored in the hit already faces the ray
+            if !from_left && self.operation == CsgOperation::Difference {
+                hit.front_face = !hit.front_face;
+            }
+            hits.push(hit);
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Capped, Sphere};
+    use crate::material::tests::gray;
+    use crate::vec3::{Point3, Vec3};
+
+    fn sphere(x: f64, radius: f64) -> Box<dyn Solid> {
+        let material = gray();
+        Box::new(Sphere::new(Point3::new(x, 0., 0.), radius, material))
+    }
+
+    fn along_x() -> Ray {
+        Ray::new(Point3::new(-5., 0., 0.), Vec3::new(1., 0., 0.))
+    }
+
+    fn ts(hits: &[HitRecord]) -> Vec<f64> {
+        hits.iter().map(|hit| hit.t).collect()
+    }
+
+    #[test]
+    fn union_removes_inner_surfaces() {
+        let union = Csg::union(sphere(-0.5, 1.), sphere(0.5, 1.));
+        assert_eq!(ts(&union.intersections(&along_x())), vec![3.5, 6.5]);
+        let hit = union.hit(&along_x(), 4., f64::INFINITY).unwrap();
+        assert_eq!(hit.t, 6.5);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn intersection_keeps_overlap() {
+        let lens = Csg::intersection(sphere(-0.5, 1.), sphere(0.5, 1.));
+        assert_eq!(ts(&lens.intersections(&along_x())), vec![4.5, 5.5]);
+        let bbox = lens.bounding_box().unwrap();
+        assert_eq!((bbox.min.x, bbox.max.x), (-0.5, 0.5));
+
+        let apart = Csg::intersection(sphere(-2., 1.), sphere(2., 1.));
+        assert!(apart.hit(&along_x(), 0., f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn difference_cuts_hole() {
+        let material = gray();
+        let hole = Capped::cylinder(Point3::new(0., -2., 0.), 0.5, 4., material);
+        let drilled = Csg::difference(sphere(0., 1.), Box::new(hole));
+
+        // Through the hole the ray passes without a hit
+        let down = Ray::new(Point3::new(0.2, 5., 0.), Vec3::new(0., -1., 0.));
+        assert!(drilled.hit(&down, 0., f64::INFINITY).is_none());
+
+        // Across the sphere the ray crosses the wall of the hole twice
+        let hits = drilled.intersections(&along_x());
+        assert_eq!(ts(&hits), vec![4., 4.5, 5.5, 6.]);
+        let front_faces: Vec<bool> = hits.iter().map(|hit| hit.front_face).collect();
+        assert_eq!(front_faces, vec![true, false, true, false]);
+        // The wall of the hole faces into the hole, against the ray leaving the sphere
+        assert!((hits[1].normal - Vec3::new(-1., 0., 0.)).length() < 1e-12);
+    }
+}
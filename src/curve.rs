@@ -0,0 +1,363 @@
+use crate::aabb::Aabb;
+use crate::geometry::quadratic_roots;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use std::sync::Arc;
+
+/// Deepest the curve is split while searching for a hit
+const MAX_DEPTH: i32 = 10;
+
+/// The cross section of a curve
+#[derive(Debug, Clone, Copy)]
+pub enum CurveShape {
+    /// A flat strip, e.g. a blade of grass, facing along the normals given at the start
+    /// and the end of the curve
+    Ribbon { normals: [Vec3; 2] },
+    /// A round tube, e.g. a hair
+    Cylinder,
+}
+
+/// A cubic Bézier curve with a width that varies linearly from the start to the end
+///
+/// The curve is intersected by splitting it in halves, skipping every part whose
+/// bounding box the ray misses, until the parts are straight enough to be treated as
+/// line segments. The texture coordinate u runs along the curve and v across it
+pub struct Curve {
+    control_points: [Point3; 4],
+    widths: [f64; 2],
+    shape: CurveShape,
+    pub material: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+/// Split a Bézier curve in the middle with de Casteljau's algorithm
+fn split([p0, p1, p2, p3]: [Point3; 4]) -> ([Point3; 4], [Point3; 4]) {
+    let (a, b, c) = (0.5 * (p0 + p1), 0.5 * (p1 + p2), 0.5 * (p2 + p3));
+    let (d, e) = (0.5 * (a + b), 0.5 * (b + c));
+    let middle = 0.5 * (d + e);
+    ([p0, a, d, middle], [middle, e, c, p3])
+}
+
+impl Curve {
+    pub fn new(
+        control_points: [Point3; 4],
+        widths: [f64; 2],
+        shape: CurveShape,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        // The curve lies inside the box around its control points
+        let half_width = 0.5 * widths[0].max(widths[1]);
+        let extent = Vec3::new(half_width, half_width, half_width);
+        let bbox = control_points
+            .iter()
+            .fold(Aabb::empty(), |bbox, p| bbox.include(p));
+        let bbox = Aabb::new(bbox.min - extent, bbox.max + extent);
+        Self {
+            control_points,
+            widths,
+            shape,
+            material,
+            bbox,
+        }
+    }
+
+    /// Split a spline of `3 n + 1` control points into its n segments, where the last
+    /// control point of each segment is the first of the next one
+    ///
+    /// The widths and ribbon normals are interpolated over the whole spline. Panics
+    /// when the number of control points is wrong
+    pub fn spline(
+        points: &[Point3],
+        widths: [f64; 2],
+        shape: CurveShape,
+        material: Arc<dyn Material>,
+    ) -> Vec<Curve> {
+        assert!(
+            points.len() >= 4 && (points.len() - 1).is_multiple_of(3),
+            "spline needs 3 n + 1 control points"
+        );
+        let segments = (points.len() - 1) / 3;
+        let at = |values: [f64; 2], i: usize| {
+            let u = i as f64 / segments as f64;
+            (1. - u) * values[0] + u * values[1]
+        };
+        (0..segments)
+            .map(|i| {
+                let shape = match shape {
+                    CurveShape::Ribbon { normals: [n0, n1] } => {
+                        let normal = |i: usize| {
+                            let u = i as f64 / segments as f64;
+                            (1. - u) * n0 + u * n1
+                        };
+                        CurveShape::Ribbon {
+                            normals: [normal(i), normal(i + 1)],
+                        }
+                    }
+                    CurveShape::Cylinder => CurveShape::Cylinder,
+                };
+                let control_points = [
+                    points[3 * i],
+                    points[3 * i + 1],
+                    points[3 * i + 2],
+                    points[3 * i + 3],
+                ];
+                let widths = [at(widths, i), at(widths, i + 1)];
+                Curve::new(control_points, widths, shape, material.clone())
+            })
+            .collect()
+    }
+
+    fn point(&self, u: f64) -> Point3 {
+        let [p0, p1, p2, p3] = self.control_points;
+        let v = 1. - u;
+        v * v * v * p0 + 3. * v * v * u * p1 + 3. * v * u * u * p2 + u * u * u * p3
+    }
+
+    fn tangent(&self, u: f64) -> Vec3 {
+        let [p0, p1, p2, p3] = self.control_points;
+        let v = 1. - u;
+        let tangent = 3. * (v * v * (p1 - p0) + 2. * v * u * (p2 - p1) + u * u * (p3 - p2));
+        // The derivative vanishes where control points coincide
+        if tangent.length_squared() > 0. {
+            tangent.unit_vector()
+        } else {
+            (p3 - p0).unit_vector()
+        }
+    }
+
+    fn half_width(&self, u: f64) -> f64 {
+        0.5 * ((1. - u) * self.widths[0] + u * self.widths[1])
+    }
+
+    /// Search the part of the curve between u0 and u1, given by its control points in
+    /// the space where the ray starts at the origin and runs along +z
+    fn search(
+        &self,
+        r: &Ray,
+        local: [Vec3; 4],
+        (u0, u1): (f64, f64),
+        depth: i32,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord<'_>> {
+        let half_width = self.half_width(u0).max(self.half_width(u1));
+        let (min, max) = local.iter().fold((local[0], local[0]), |(min, max), p| {
+            (min.min(p), max.max(p))
+        });
+        let speed = r.dir.length();
+        let outside =
+            |min: f64, max: f64, lo: f64, hi: f64| min - half_width > hi || max + half_width < lo;
+        if outside(min.x, max.x, 0., 0.)
+            || outside(min.y, max.y, 0., 0.)
+            || outside(min.z, max.z, t_min * speed, t_max * speed)
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let (first, second) = split(local);
+            let middle = 0.5 * (u0 + u1);
+            let hit = self.search(r, first, (u0, middle), depth - 1, t_min, t_max);
+            let t_max = hit.as_ref().map_or(t_max, |hit| hit.t);
+            return self
+                .search(r, second, (middle, u1), depth - 1, t_min, t_max)
+                .or(hit);
+        }
+
+        // The part is straight enough to be a line segment, find the point on it
+        // closest to the ray
+        let (start, end) = (local[0], local[3]);
+        let chord = Vec3::new(end.x - start.x, end.y - start.y, 0.);
+        let w = if chord.length_squared() > 0. {
+            -Vec3::new(start.x, start.y, 0.).dot(&chord) / chord.length_squared()
+        } else {
+            0.
+        };
+        // Beyond the ends of the whole curve there is nothing to hit
+        if (u0 == 0. && w < 0.) || (u1 == 1. && w > 1.) {
+            return None;
+        }
+        let u = u0 + w.clamp(0., 1.) * (u1 - u0);
+        self.exact_hit(r, u, (end - start).length(), t_min, t_max)
+    }
+
+    /// Intersect the ray with the cross section of the curve around the point at u,
+    /// accepting hits up to the given distance along the curve from that point
+    fn exact_hit(
+        &self,
+        r: &Ray,
+        u: f64,
+        reach: f64,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord<'_>> {
+        let center = self.point(u);
+        let tangent = self.tangent(u);
+        let half_width = self.half_width(u);
+        let across = |v: Vec3| v - tangent * v.dot(&tangent);
+
+        let (t, normal) = match self.shape {
+            CurveShape::Cylinder => {
+                // The ray against the tube around the tangent line, seen along the tangent
+                let (o, d) = (across(r.origin - center), across(r.dir));
+                let (t0, t1) = quadratic_roots(
+                    d.dot(&d),
+                    2. * o.dot(&d),
+                    o.dot(&o) - half_width * half_width,
+                )?;
+                let t = if t0 > t_min && t0 < t_max { t0 } else { t1 };
+                (t, across(r.at(t) - center).unit_vector())
+            }
+            CurveShape::Ribbon { normals: [n0, n1] } => {
+                let normal = across((1. - u) * n0 + u * n1);
+                if normal.length_squared() == 0. {
+                    return None;
+                }
+                let normal = normal.unit_vector();
+                let denominator = r.dir.dot(&normal);
+                if denominator == 0. {
+                    return None;
+                }
+                ((center - r.origin).dot(&normal) / denominator, normal)
+            }
+        };
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let p = r.at(t);
+        let offset = p - center;
+        if offset.dot(&tangent).abs() > reach {
+            return None;
+        }
+        // v runs across the curve as seen along the ray for tubes, and across the strip
+        // for ribbons
+        let side = match self.shape {
+            CurveShape::Cylinder => tangent.cross(&r.dir),
+            CurveShape::Ribbon { .. } => tangent.cross(&normal),
+        };
+        let distance = if side.length_squared() > 0. {
+            offset.dot(&side.unit_vector())
+        } else {
+            0.
+        };
+        if distance.abs() > half_width {
+            return None;
+        }
+        let v = 0.5 + 0.5 * distance / half_width;
+        Some(HitRecord::new(r, p, &normal, t, &*self.material).with_uv(u, v))
+    }
+}
+
+impl Hittable for Curve {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+        // A space where the ray starts at the origin and runs along +z, so the curve
+        // is hit where it passes the origin in x and y
+        let z = r.dir.unit_vector();
+        let helper = if z.x.abs() > 0.9 {
+            Vec3::new(0., 1., 0.)
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let x = helper.cross(&z).unit_vector();
+        let y = z.cross(&x);
+        let local = self.control_points.map(|p| {
+            let offset = p - r.origin;
+            Vec3::new(offset.dot(&x), offset.dot(&y), offset.dot(&z))
+        });
+
+        // Split until the parts deviate from straight lines by a fraction of the width
+        let [p0, p1, p2, p3] = local;
+        let bend = [p0 - 2. * p1 + p2, p1 - 2. * p2 + p3]
+            .iter()
+            .flat_map(|v| [v.x.abs(), v.y.abs(), v.z.abs()])
+            .fold(0., f64::max);
+        let tolerance = 0.05 * self.widths[0].max(self.widths[1]);
+        let depth = if bend > 0. && tolerance > 0. {
+            ((2f64.sqrt() * 6. * bend / (8. * tolerance)).log2() / 2.).clamp(0., MAX_DEPTH as f64)
+                as i32
+        } else {
+            0
+        };
+        self.search(r, local, (0., 1.), depth, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::gray;
+
+    /// A straight curve from the origin to (1, 0, 0)
+    fn straight(shape: CurveShape) -> Curve {
+        let points = [0., 1. / 3., 2. / 3., 1.].map(|x| Point3::new(x, 0., 0.));
+        Curve::new(points, [0.2, 0.2], shape, gray())
+    }
+
+    #[test]
+    fn cylinder_is_round() {
+        let tube = straight(CurveShape::Cylinder);
+        let down = |x: f64, y: f64| Ray::new(Point3::new(x, y, 5.), Vec3::new(0., 0., -2.));
+        let hit = tube.hit(&down(0.5, 0.), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.p - Point3::new(0.5, 0., 0.1)).length() < 1e-9);
+        assert!((hit.normal - Vec3::new(0., 0., 1.)).length() < 1e-9);
+        assert!((hit.u - 0.5).abs() < 1e-9);
+
+        // Off center the normal tilts to the side
+        let hit = tube.hit(&down(0.25, 0.05), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.normal - Vec3::new(0., 0.5, 0.75f64.sqrt())).length() < 1e-9);
+
+        assert!(tube.hit(&down(0.5, 0.15), 0.001, f64::INFINITY).is_none());
+        assert!(tube.hit(&down(1.05, 0.), 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn ribbon_faces_its_normals() {
+        let up = Vec3::new(0., 1., 0.);
+        let ribbon = straight(CurveShape::Ribbon { normals: [up, up] });
+        let down = Ray::new(Point3::new(0.5, 5., 0.05), Vec3::new(0., -1., 0.));
+        let hit = ribbon.hit(&down, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 5.).abs() < 1e-9);
+        assert!((hit.normal - up).length() < 1e-9);
+        assert!((hit.v - 0.75).abs() < 1e-9);
+
+        // Seen edge on the ribbon has no width
+        let edge_on = Ray::new(Point3::new(0.5, 0., 5.), Vec3::new(0., 0., -1.));
+        assert!(ribbon.hit(&edge_on, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn follows_bent_spline() {
+        // Two segments forming an arch from (-1, 0, 0) over (0, 1, 0) to (1, 0, 0)
+        let points = [
+            Point3::new(-1., 0., 0.),
+            Point3::new(-1., 0.5, 0.),
+            Point3::new(-0.5, 1., 0.),
+            Point3::new(0., 1., 0.),
+            Point3::new(0.5, 1., 0.),
+            Point3::new(1., 0.5, 0.),
+            Point3::new(1., 0., 0.),
+        ];
+        let arch = Curve::spline(&points, [0.1, 0.05], CurveShape::Cylinder, gray());
+        assert_eq!(arch.len(), 2);
+
+        let ray = Ray::new(Point3::new(0., 1., 5.), Vec3::new(0., 0., -1.));
+        let hit = arch[1].hit(&ray, 0.001, f64::INFINITY).unwrap();
+        // The width at the top is halfway between the start and the end
+        assert!((hit.t - (5. - 0.0375)).abs() < 1e-6);
+        assert!((hit.normal - Vec3::new(0., 0., 1.)).length() < 1e-6);
+        // Under the arch there is nothing
+        let below = Ray::new(Point3::new(0., 0.5, 5.), Vec3::new(0., 0., -1.));
+        assert!(arch
+            .iter()
+            .all(|part| part.hit(&below, 0.001, f64::INFINITY).is_none()));
+    }
+}
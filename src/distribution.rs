@@ -0,0 +1,157 @@
+/// A piecewise-constant distribution over [0, 1), split into equal parts that are
+/// picked with a probability proportional to their weights
+///
+/// When all weights are zero every part is equally likely
+pub struct Distribution1D {
+    weights: Vec<f64>,
+    /// Cumulative probability at the start of each part, ending with 1
+    cdf: Vec<f64>,
+    /// Integral of the weights over [0, 1)
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// Panics when there are no weights, negative weights count as zero
+    pub fn new(weights: Vec<f64>) -> Self {
+        assert!(
+            !weights.is_empty(),
+            "distribution needs at least one weight"
+        );
+        let n = weights.len() as f64;
+        let weights: Vec<f64> = weights
+            .into_iter()
+            .map(|weight| if weight > 0. { weight } else { 0. })
+            .collect();
+        let total: f64 = weights.iter().sum();
+
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        cdf.push(0.);
+        for (i, weight) in weights.iter().enumerate() {
+            let next = if total > 0. {
+                cdf[i] + weight / total
+            } else {
+                (i + 1) as f64 / n
+            };
+            cdf.push(next);
+        }
+        Self {
+            weights,
+            cdf,
+            integral: total / n,
+        }
+    }
+
+    /// Integral of the weights over [0, 1)
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Map a uniform random number in [0, 1) to a point in [0, 1) following the
+    /// distribution, returning the point, its density and the index of its part
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let n = self.weights.len();
+        // The last part that starts at or before u
+        let index = (self.cdf.partition_point(|&c| c <= u) - 1).min(n - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0. {
+            (u - self.cdf[index]) / width
+        } else {
+            0.
+        };
+        let x = ((index as f64 + offset) / n as f64).min(1. - f64::EPSILON);
+        (x, self.pdf(x), index)
+    }
+
+    /// Density of the distribution at a point in [0, 1)
+    pub fn pdf(&self, x: f64) -> f64 {
+        let n = self.weights.len();
+        let index = ((x * n as f64) as usize).min(n - 1);
+        if self.integral > 0. {
+            self.weights[index] / self.integral
+        } else {
+            1.
+        }
+    }
+}
+
+/// A piecewise-constant distribution over [0, 1)², split into a grid of cells that
+/// are picked with a probability proportional to their weights
+///
+/// A row is picked first by the total weight of its cells, then a cell within the row
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Create a distribution from `width * height` weights, stored row by row
+    ///
+    /// Panics when the number of weights does not match
+    pub fn new(width: usize, height: usize, weights: &[f64]) -> Self {
+        assert_eq!(weights.len(), width * height, "wrong number of weights");
+        let rows: Vec<Distribution1D> = weights
+            .chunks_exact(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::integral).collect());
+        Self { rows, marginal }
+    }
+
+    /// Map two uniform random numbers in [0, 1) to a point in [0, 1)² following the
+    /// distribution, returning the point as (x, y) and its density
+    pub fn sample(&self, u: f64, v: f64) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample(v);
+        let (x, pdf_x, _) = self.rows[row].sample(u);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    /// Density of the distribution at a point in [0, 1)²
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let row = ((y * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+        self.rows[row].pdf(x) * self.marginal.pdf(y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_weights() {
+        let distribution = Distribution1D::new(vec![1., 0., 3.]);
+        assert!((distribution.integral() - 4. / 3.).abs() < 1e-12);
+        // A quarter of the probability lies in the first part
+        let (x, pdf, index) = distribution.sample(0.125);
+        assert_eq!(index, 0);
+        assert!((x - 1. / 6.).abs() < 1e-12);
+        assert!((pdf - 0.75).abs() < 1e-12);
+        let (x, pdf, index) = distribution.sample(0.625);
+        assert_eq!(index, 2);
+        assert!((x - 5. / 6.).abs() < 1e-12);
+        assert!((pdf - 2.25).abs() < 1e-12);
+        assert_eq!(distribution.pdf(0.5), 0.);
+
+        let uniform = Distribution1D::new(vec![0., 0.]);
+        let (x, pdf, _) = uniform.sample(0.3);
+        assert!((x - 0.3).abs() < 1e-12);
+        assert_eq!(pdf, 1.);
+    }
+
+    #[test]
+    fn picks_bright_cells() {
+        // Only the bottom right cell and the top left cell have weight
+        let distribution = Distribution2D::new(2, 2, &[1., 0., 0., 3.]);
+        let ((x, y), pdf) = distribution.sample(0.5, 0.5);
+        assert!(x >= 0.5 && y >= 0.5);
+        assert!((pdf - 3.).abs() < 1e-12);
+        assert!((distribution.pdf(x, y) - pdf).abs() < 1e-12);
+        assert_eq!(distribution.pdf(0.75, 0.25), 0.);
+
+        // The density integrates to 1 over the cells
+        let total: f64 = [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+            .iter()
+            .map(|&(x, y)| distribution.pdf(x, y) * 0.25)
+            .sum();
+        assert!((total - 1.).abs() < 1e-12);
+    }
+}
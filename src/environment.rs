@@ -0,0 +1,440 @@
+use crate::color::{self, Color};
+use crate::distribution::Distribution2D;
+use crate::load::{self, LoadError};
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+use rand::Rng;
+use std::f64::consts::PI;
+use std::path::Path;
+
+/// Light arriving from infinitely far away, seen by every ray that leaves the scene
+pub trait Environment: Send + Sync {
+    /// Light arriving from the direction, which need not have unit length
+    fn color(&self, direction: &Vec3) -> Color;
+
+    /// Pick a direction to sample the light of the environment, more likely where it
+    /// is brighter, returning the unit direction, the light from there and the density
+    /// of picking it with respect to solid angle
+    ///
+    /// Environments that are not worth sampling, e.g. because they are smooth, return
+    /// nothing and are only found by rays that scatter into them
+    fn sample(&self, _sampler: &mut Sampler) -> Option<(Vec3, Color, f64)> {
+        None
+    }
+
+    /// Density with respect to solid angle of [`sample`](Environment::sample) picking
+    /// the direction
+    fn pdf(&self, _direction: &Vec3) -> f64 {
+        0.
+    }
+}
+
+/// The same color in every direction, black gives a scene that is only lit by its
+/// lights
+pub struct SolidEnvironment {
+    pub color: Color,
+}
+
+impl SolidEnvironment {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for SolidEnvironment {
+    fn color(&self, _direction: &Vec3) -> Color {
+        self.color
+    }
+}
+
+/// A vertical gradient from the color straight down to the color straight up
+pub struct GradientEnvironment {
+    pub bottom: Color,
+    pub top: Color,
+}
+
+impl GradientEnvironment {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+}
+
+/// A blue sky that is white at the bottom
+impl Default for GradientEnvironment {
+    fn default() -> Self {
+        Self::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for GradientEnvironment {
+    fn color(&self, direction: &Vec3) -> Color {
+        // Scale y from [-1, 1] to [0, 1]
+        let t = 0.5 * (direction.unit_vector().y + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+/// An environment from an equirectangular image, e.g. a high dynamic range photo of
+/// the sky
+///
+/// The top row of the image is straight up, and the center of the image is in the -z
+/// direction. Pixels are not filtered, each covers a rectangle of longitude and
+/// latitude. Directions are sampled by the luminance of the pixels, so a small bright
+/// sun is found without relying on rays to scatter into it
+pub struct ImageEnvironment {
+    width: usize,
+    height: usize,
+    /// Pixels stored row by row, starting at the top left
+    pixels: Vec<Color>,
+    /// Rotation around the y axis in degrees, counter-clockwise seen from above
+    rotation: f64,
+    /// Factor all pixels are multiplied by
+    intensity: f64,
+    /// Picks pixels by their share of the light
+    distribution: Distribution2D,
+}
+
+impl ImageEnvironment {
+    /// Create an environment from the pixels of an image, stored row by row starting
+    /// at the top left
+    ///
+    /// Panics when the number of pixels does not match the size
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "environment needs at least one pixel"
+        );
+        assert_eq!(pixels.len(), width * height, "environment size mismatch");
+        // Rows near the poles cover a smaller solid angle
+        let weights: Vec<f64> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, pixel)| {
+                let latitude = PI * ((i / width) as f64 + 0.5) / height as f64;
+                color::luminance(pixel) * latitude.sin()
+            })
+            .collect();
+        let distribution = Distribution2D::new(width, height, &weights);
+        Self {
+            width,
+            height,
+            pixels,
+            rotation: 0.,
+            intensity: 1.,
+            distribution,
+        }
+    }
+
+    /// Load a Radiance (.hdr) or OpenEXR (.exr) image, chosen by the extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let error = |message: String| LoadError {
+            path: Some(path.to_path_buf()),
+            message,
+        };
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let decode = match extension.as_deref() {
+            Some("hdr") => decode_hdr,
+            Some("exr") => decode_exr,
+            _ => {
+                return Err(error(
+                    "environment image must be a .hdr or .exr file".into(),
+                ))
+            }
+        };
+        let data = load::read_file(path)?;
+        let (width, height, pixels) = decode(&data).map_err(error)?;
+        Ok(Self::new(width, height, pixels))
+    }
+
+    /// Rotate the environment around the y axis, counter-clockwise seen from above
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    /// Multiply the brightness of the environment
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Position in the image of a direction, with u from left to right and v from top
+    /// to bottom
+    fn direction_to_uv(&self, direction: &Vec3) -> (f64, f64) {
+        let d = direction.unit_vector();
+        // Undo the rotation of the environment
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (x, z) = (cos * d.x - sin * d.z, sin * d.x + cos * d.z);
+        let longitude = x.atan2(-z);
+        let latitude = d.y.clamp(-1., 1.).acos();
+        (0.5 + longitude / (2. * PI), latitude / PI)
+    }
+
+    /// The unit direction of a position in the image
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let (sin_longitude, cos_longitude) = ((u - 0.5) * 2. * PI).sin_cos();
+        let (sin_latitude, cos_latitude) = (v * PI).sin_cos();
+        let (x, z) = (sin_latitude * sin_longitude, -sin_latitude * cos_longitude);
+        // Apply the rotation of the environment
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        Vec3::new(cos * x + sin * z, cos_latitude, cos * z - sin * x)
+    }
+}
+
+impl Environment for ImageEnvironment {
+    fn color(&self, direction: &Vec3) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        let column = ((u * self.width as f64) as usize).min(self.width - 1);
+        let row = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.intensity * self.pixels[row * self.width + column]
+    }
+
+    fn sample(&self, sampler: &mut Sampler) -> Option<(Vec3, Color, f64)> {
+        let ((u, v), pdf) = self.distribution.sample(sampler.gen(), sampler.gen());
+        // The image is stretched over the sphere, less so towards the poles
+        let stretch = 2. * PI * PI * (v * PI).sin();
+        if pdf == 0. || stretch <= 0. {
+            return None;
+        }
+        let direction = self.uv_to_direction(u, v);
+        Some((direction, self.color(&direction), pdf / stretch))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let stretch = 2. * PI * PI * (v * PI).sin();
+        if stretch <= 0. {
+            return 0.;
+        }
+        self.distribution.pdf(u, v) / stretch
+    }
+}
+
+/// Decode a Radiance RGBE image to its size and pixels, stored row by row starting at
+/// the top left
+///
+/// Only the standard orientation, rows from top to bottom, is supported
+pub(crate) fn decode_hdr(data: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    let mut rest = data;
+    let mut line = || {
+        let end = rest.iter().position(|&byte| byte == b'\n')?;
+        let line = String::from_utf8_lossy(&rest[..end]).into_owned();
+        rest = &rest[end + 1..];
+        Some(line)
+    };
+    let truncated = || "file ends early".to_string();
+
+    if !line().ok_or_else(truncated)?.starts_with("#?") {
+        return Err("not a Radiance HDR file".into());
+    }
+    loop {
+        let header = line().ok_or_else(truncated)?;
+        if header.is_empty() {
+            break;
+        }
+        if let Some(format) = header.strip_prefix("FORMAT=") {
+            if format.trim() != "32-bit_rle_rgbe" {
+                return Err(format!("unsupported pixel format {}", format.trim()));
+            }
+        }
+    }
+    let resolution = line().ok_or_else(truncated)?;
+    let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (
+            width.parse::<usize>().map_err(|e| e.to_string())?,
+            height.parse::<usize>().map_err(|e| e.to_string())?,
+        ),
+        _ => return Err(format!("unsupported image orientation `{}`", resolution)),
+    };
+    if width == 0 || height == 0 {
+        return Err("image is empty".into());
+    }
+
+    let mut bytes = rest.iter().copied();
+    let mut next = || bytes.next().ok_or_else(truncated);
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut row = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        let start = [next()?, next()?, next()?, next()?];
+        let run_length = (8..32768).contains(&width)
+            && start[0] == 2
+            && start[1] == 2
+            && ((start[2] as usize) << 8 | start[3] as usize) == width;
+        if run_length {
+            // Each channel is stored separately, as runs and literal spans of bytes
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = next()? as usize;
+                    let (count, repeated) = if count > 128 {
+                        (count - 128, Some(next()?))
+                    } else {
+                        (count, None)
+                    };
+                    if count == 0 || x + count > width {
+                        return Err("invalid run length encoding".into());
+                    }
+                    for pixel in &mut row[x..x + count] {
+                        pixel[channel] = match repeated {
+                            Some(value) => value,
+                            None => next()?,
+                        };
+                    }
+                    x += count;
+                }
+            }
+        } else {
+            row[0] = start;
+            for pixel in &mut row[1..] {
+                *pixel = [next()?, next()?, next()?, next()?];
+            }
+        }
+        pixels.extend(row.iter().map(|&[r, g, b, e]| {
+            if e == 0 {
+                Color::zero()
+            } else {
+                // The mantissas are fractions of 256 with a shared exponent
+                let scale = 2f64.powi(e as i32 - 136);
+                Color::new(r as f64 * scale, g as f64 * scale, b as f64 * scale)
+            }
+        }));
+    }
+    Ok((width, height, pixels))
+}
+
+/// Decode the first RGB layer of an OpenEXR image to its size and pixels, stored row
+/// by row starting at the top left
+pub(crate) fn decode_exr(data: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    use exr::prelude::{read, ReadChannels, ReadLayers};
+
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .rgba_channels(
+            |resolution, _| {
+                let size = (resolution.width(), resolution.height());
+                (size, vec![Color::zero(); size.0 * size.1])
+            },
+            |(size, pixels): &mut ((usize, usize), Vec<Color>),
+             position,
+             (r, g, b, _): (f32, f32, f32, f32)| {
+                pixels[position.y() * size.0 + position.x()] =
+                    Color::new(r as f64, g as f64, b as f64);
+            },
+        )
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(std::io::Cursor::new(data))
+        .map_err(|e| e.to_string())?;
+    let ((width, height), pixels) = image.layer_data.channel_data.pixels;
+    if width == 0 || height == 0 {
+        return Err("image is empty".into());
+    }
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+    use crate::render::Sample;
+    use crate::sampler::Sampler;
+
+    #[test]
+    fn gradient_is_sky() {
+        let sky = GradientEnvironment::default();
+        let up = sky.color(&Vec3::new(0., 2., 0.));
+        assert!((up - Color::new(0.5, 0.7, 1.0)).length() < 1e-12);
+        let horizon = sky.color(&Vec3::new(1., 0., 0.));
+        assert!((horizon - Color::new(0.75, 0.85, 1.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn image_directions() {
+        // Four columns of different colors, the top row bright and the bottom one dark
+        let colors = [0.1, 0.2, 0.3, 0.4];
+        let pixels = [1., 0.5]
+            .iter()
+            .flat_map(|row| colors.iter().map(move |c| Color::new(*c, 0., 0.) * *row))
+            .collect();
+        let environment = ImageEnvironment::new(4, 2, pixels);
+        let red = |x: f64, y: f64, z: f64| environment.color(&Vec3::new(x, y, z)).x;
+        // The center of the image looks along -z, the left edge along +z
+        assert!((red(0., 0.1, -1.) - 0.3).abs() < 1e-12);
+        assert!((red(0., -0.1, -1.) - 0.15).abs() < 1e-12);
+        assert!((red(-1., 0.1, 0.) - 0.2).abs() < 1e-12);
+        assert!((red(1., 0.1, 0.) - 0.4).abs() < 1e-12);
+
+        // After turning a quarter to the left, -z shows what was at +x
+        let environment = environment.with_rotation(90.).with_intensity(2.);
+        let red = |x: f64, y: f64, z: f64| environment.color(&Vec3::new(x, y, z)).x;
+        assert!((red(0., 0.1, -1.) - 0.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn samples_bright_pixels() {
+        // A dark sky with one bright pixel
+        let mut pixels = vec![Color::new(0.1, 0.1, 0.1); 8 * 4];
+        pixels[8 + 5] = Color::new(100., 100., 100.);
+        let environment = ImageEnvironment::new(8, 4, pixels).with_rotation(45.);
+        let mut sampler = Sampler::from_seed(1);
+        let mut bright = 0;
+        for _ in 0..1000 {
+            let (direction, light, pdf) = environment.sample(&mut sampler).unwrap();
+            assert!((direction.length() - 1.).abs() < 1e-12);
+            assert!((environment.pdf(&direction) - pdf).abs() < 1e-9 * pdf);
+            assert!((environment.color(&direction) - light).length() < 1e-12);
+            if light.x > 1. {
+                bright += 1;
+            }
+        }
+        assert!(bright > 900);
+    }
+
+    #[test]
+    fn decodes_hdr() {
+        let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 8\n".to_vec();
+        // A flat row of gray pixels at 1, 2, 4, ... 128
+        for i in 0..8 {
+            data.extend([128, 128, 128, 129 + i]);
+        }
+        // A run length encoded row, black except for the red channel
+        data.extend([2, 2, 0, 8]);
+        data.extend([128 + 8, 128]);
+        data.extend([128 + 8, 0, 128 + 8, 0]);
+        data.extend([4, 129, 130, 131, 132, 128 + 4, 133]);
+        let (width, height, pixels) = decode_hdr(&data).unwrap();
+        assert_eq!((width, height), (8, 2));
+        assert_eq!(pixels[0].y, 1.);
+        assert_eq!(pixels[7].z, 128.);
+        assert_eq!(pixels[8].x, 1.);
+        assert_eq!(pixels[11].x, 8.);
+        assert_eq!(pixels[15].x, 16.);
+        assert_eq!(pixels[15].y, 0.);
+
+        assert!(decode_hdr(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn decodes_exr() {
+        let mut image = Image::new(2, 1);
+        for x in 0..2 {
+            let sample = Sample {
+                color: Color::new(x as f64 + 0.5, 2., 3.),
+                albedo: Color::zero(),
+                normal: Vec3::zero(),
+                depth: 1.,
+            };
+            image.add_sample(x, 0, &sample);
+        }
+        let mut data = Vec::new();
+        image.write_exr(&mut data).unwrap();
+        let (width, height, pixels) = decode_exr(&data).unwrap();
+        assert_eq!((width, height), (2, 1));
+        assert!((pixels[1] - Color::new(1.5, 2., 3.)).length() < 1e-6);
+    }
+}
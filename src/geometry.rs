@@ -1,33 +1,52 @@
+use crate::aabb::Aabb;
+use crate::color::Color;
 use crate::ray::Ray;
 use crate::vec3;
-use crate::{hittable::HitRecord, hittable::Hittable, material::Material, vec3::Point3};
+use crate::{
+    hittable::HitRecord, hittable::Hittable, hittable::Solid, material::Material, vec3::Point3,
+    vec3::Vec3,
+};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Thickness given to the bounding boxes of flat objects
+const FLAT_PADDING: f64 = 0.0001;
 
 pub struct Sphere {
     pub center: Point3,
     pub radius: f64,
-    pub material: Box<dyn Material>,
+    pub material: Arc<dyn Material>,
 }
 
 impl Sphere {
-
-    pub fn new(center: Point3, radius: f64, material: Box<dyn Material>) -> Self {
+    pub fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
         Self {
             center,
             radius,
-            material
+            material,
         }
     }
 
     /// Create a HitRecord for a ray and a t that has been hit
-    fn create_record(&self, ray: &Ray, hit_at_t: f64) -> HitRecord {
+    fn create_record(&self, ray: &Ray, hit_at_t: f64) -> HitRecord<'_> {
         let hit_point = ray.at(hit_at_t);
         let normal = (hit_point - self.center) / self.radius;
-        HitRecord::new(&ray, hit_point.clone(), &normal, hit_at_t, &*self.material)
+        let (u, v) = sphere_uv(&normal);
+        HitRecord::new(ray, hit_point, &normal, hit_at_t, &*self.material).with_uv(u, v)
     }
 }
 
+/// Texture coordinates on the unit sphere
+///
+/// u goes around the y axis starting at -x, v goes from the bottom to the top
+fn sphere_uv(p: &Point3) -> (f64, f64) {
+    let theta = (-p.y).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2. * PI), theta / PI)
+}
+
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<crate::hittable::HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         // Use quadratic formula for collisions
         let oc = r.origin - self.center;
         let a = r.dir.length_squared();
@@ -53,4 +72,1440 @@ impl Hittable for Sphere {
 
         None
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+impl Solid for Sphere {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        let oc = r.origin - self.center;
+        let a = r.dir.length_squared();
+        let b = 2. * oc.dot(&r.dir);
+        let c = oc.length_squared() - self.radius * self.radius;
+        match quadratic_roots(a, b, c) {
+            Some((t0, t1)) if t0 < t1 => {
+                vec![self.create_record(r, t0), self.create_record(r, t1)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// An infinite plane
+pub struct Plane {
+    /// A point on the plane
+    pub point: Point3,
+    /// Outward unit normal of the plane
+    pub normal: Vec3,
+    pub material: Arc<dyn Material>,
+    /// Direction of the u texture coordinate on the plane
+    tangent: Vec3,
+    /// Direction of the v texture coordinate on the plane
+    bitangent: Vec3,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: Arc<dyn Material>) -> Self {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = tangent_basis(&normal);
+        Self {
+            point,
+            normal,
+            material,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denominator = self.normal.dot(&r.dir);
+        // The ray runs parallel to the plane
+        if denominator.abs() < 1e-12 {
+            return None;
+        }
+        let t = (self.point - r.origin).dot(&self.normal) / denominator;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let hit_point = r.at(t);
+        // The texture coordinates are the distances along the plane, so textures repeat
+        let offset = hit_point - self.point;
+        let (u, v) = (offset.dot(&self.tangent), offset.dot(&self.bitangent));
+        Some(HitRecord::new(r, hit_point, &self.normal, t, &*self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// Intersect a ray with a rectangle in the plane `axes.2 == k`
+///
+/// The rectangle spans [a0, a1] along `axes.0` and [b0, b1] along `axes.1`, the
+/// outward normal points in the positive direction of `axes.2`
+#[allow(clippy::too_many_arguments)]
+fn hit_axis_rect<'a>(
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+    axes: (usize, usize, usize),
+    (a0, a1): (f64, f64),
+    (b0, b1): (f64, f64),
+    k: f64,
+    material: &'a dyn Material,
+) -> Option<HitRecord<'a>> {
+    let (a_axis, b_axis, k_axis) = axes;
+    let t = (k - r.origin[k_axis]) / r.dir[k_axis];
+    // Also rejects the NaN of a ray running parallel to the rectangle
+    if !(t > t_min && t < t_max) {
+        return None;
+    }
+    let a = r.origin[a_axis] + t * r.dir[a_axis];
+    let b = r.origin[b_axis] + t * r.dir[b_axis];
+    if a < a0 || a > a1 || b < b0 || b > b1 {
+        return None;
+    }
+
+    let mut normal = Vec3::zero();
+    match k_axis {
+        0 => normal.x = 1.,
+        1 => normal.y = 1.,
+        _ => normal.z = 1.,
+    }
+    let (u, v) = ((a - a0) / (a1 - a0), (b - b0) / (b1 - b0));
+    Some(HitRecord::new(r, r.at(t), &normal, t, material).with_uv(u, v))
+}
+
+/// A rectangle in the plane z = k, with its normal pointing towards +z
+pub struct XyRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub y0: f64,
+    pub y1: f64,
+    pub k: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl XyRect {
+    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for XyRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_axis_rect(
+            r,
+            t_min,
+            t_max,
+            (0, 1, 2),
+            (self.x0, self.x1),
+            (self.y0, self.y1),
+            self.k,
+            &*self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point3::new(self.x0, self.y0, self.k - FLAT_PADDING),
+            Point3::new(self.x1, self.y1, self.k + FLAT_PADDING),
+        ))
+    }
+}
+
+/// A rectangle in the plane y = k, with its normal pointing towards +y
+pub struct XzRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl XzRect {
+    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for XzRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_axis_rect(
+            r,
+            t_min,
+            t_max,
+            (0, 2, 1),
+            (self.x0, self.x1),
+            (self.z0, self.z1),
+            self.k,
+            &*self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point3::new(self.x0, self.k - FLAT_PADDING, self.z0),
+            Point3::new(self.x1, self.k + FLAT_PADDING, self.z1),
+        ))
+    }
+}
+
+/// A rectangle in the plane x = k, with its normal pointing towards +x
+pub struct YzRect {
+    pub y0: f64,
+    pub y1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl YzRect {
+    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for YzRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_axis_rect(
+            r,
+            t_min,
+            t_max,
+            (1, 2, 0),
+            (self.y0, self.y1),
+            (self.z0, self.z1),
+            self.k,
+            &*self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point3::new(self.k - FLAT_PADDING, self.y0, self.z0),
+            Point3::new(self.k + FLAT_PADDING, self.y1, self.z1),
+        ))
+    }
+}
+
+/// Intersection with the surface of an analytic shape, before it becomes a HitRecord
+struct SurfaceHit {
+    t: f64,
+    /// Outward unit normal
+    normal: Vec3,
+    uv: (f64, f64),
+}
+
+impl SurfaceHit {
+    fn record<'a>(self, r: &Ray, material: &'a dyn Material) -> HitRecord<'a> {
+        let (u, v) = self.uv;
+        HitRecord::new(r, r.at(self.t), &self.normal, self.t, material).with_uv(u, v)
+    }
+}
+
+/// The nearest of two optional hits
+fn nearest(a: Option<SurfaceHit>, b: Option<SurfaceHit>) -> Option<SurfaceHit> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.t < a.t { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Hit records for the hits of a solid, ordered by t
+fn ordered_records<'a>(
+    r: &Ray,
+    hits: impl IntoIterator<Item = SurfaceHit>,
+    material: &'a dyn Material,
+) -> Vec<HitRecord<'a>> {
+    let mut hits: Vec<SurfaceHit> = hits.into_iter().collect();
+    hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+    hits.into_iter()
+        .map(|hit| hit.record(r, material))
+        .collect()
+}
+
+/// Roots of a * t^2 + b * t + c in increasing order, also handles a linear equation
+pub(crate) fn quadratic_roots(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0. {
+        let root = -c / b;
+        return if root.is_finite() {
+            Some((root, root))
+        } else {
+            None
+        };
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    // Avoid the cancellation of the textbook formula
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (t0, t1) = if q == 0. { (0., 0.) } else { (q / a, c / q) };
+    Some(if t0 < t1 { (t0, t1) } else { (t1, t0) })
+}
+
+/// Evaluate a polynomial with its coefficients ordered from the constant term up
+fn evaluate_polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0., |sum, c| sum * x + c)
+}
+
+/// Real roots of a polynomial in [lo, hi] in increasing order, the coefficients are
+/// ordered from the constant term up
+///
+/// The roots of the derivative split the interval into parts where the polynomial is
+/// monotonic. Each part contains at most one root, which is found by bisection. Roots
+/// where the polynomial only touches zero without changing sign can be missed
+pub(crate) fn polynomial_roots(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let degree = match coefficients.iter().rposition(|&c| c != 0.) {
+        Some(degree) => degree,
+        None => return Vec::new(),
+    };
+    let coefficients = &coefficients[..=degree];
+    if degree == 0 {
+        return Vec::new();
+    }
+    if degree == 1 {
+        let root = -coefficients[0] / coefficients[1];
+        return if root >= lo && root <= hi {
+            vec![root]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let derivative: Vec<f64> = coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(power, c)| power as f64 * c)
+        .collect();
+    let mut bounds = vec![lo];
+    bounds.extend(polynomial_roots(&derivative, lo, hi));
+    bounds.push(hi);
+
+    let mut roots: Vec<f64> = Vec::new();
+    let mut push = |root: f64| {
+        if roots.last() != Some(&root) {
+            roots.push(root);
+        }
+    };
+    for part in bounds.windows(2) {
+        let (mut a, mut b) = (part[0], part[1]);
+        let (fa, fb) = (
+            evaluate_polynomial(coefficients, a),
+            evaluate_polynomial(coefficients, b),
+        );
+        if fa == 0. {
+            push(a);
+        } else if fb != 0. && (fa < 0.) != (fb < 0.) {
+            // Bisect until the interval can not get any smaller
+            loop {
+                let middle = 0.5 * (a + b);
+                if middle <= a || middle >= b {
+                    break;
+                }
+                let f = evaluate_polynomial(coefficients, middle);
+                if f == 0. {
+                    a = middle;
+                    b = middle;
+                    break;
+                }
+                if (f < 0.) == (fa < 0.) {
+                    a = middle;
+                } else {
+                    b = middle;
+                }
+            }
+            push(0.5 * (a + b));
+        }
+        if fb == 0. {
+            push(b);
+        }
+    }
+    roots
+}
+
+/// Two unit vectors that together with the normal form an orthonormal basis
+fn tangent_basis(normal: &Vec3) -> (Vec3, Vec3) {
+    // Any vector that is not parallel to the normal gives a basis on the plane
+    let helper = if normal.x.abs() > 0.9 {
+        Vec3::new(0., 1., 0.)
+    } else {
+        Vec3::new(1., 0., 0.)
+    };
+    let tangent = helper.cross(normal).unit_vector();
+    (tangent, normal.cross(&tangent))
+}
+
+/// An axis-aligned box
+///
+/// Named `Cuboid` so it does not shadow [`std::boxed::Box`]
+pub struct Cuboid {
+    /// Corner with the smallest coordinates
+    pub min: Point3,
+    /// Corner with the largest coordinates
+    pub max: Point3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cuboid {
+    /// Create a box spanning the two opposite corners
+    pub fn new(a: Point3, b: Point3, material: Arc<dyn Material>) -> Self {
+        Self {
+            min: a.min(&b),
+            max: a.max(&b),
+            material,
+        }
+    }
+
+    /// The surface hit on the face perpendicular to an axis
+    fn face_hit(&self, r: &Ray, t: f64, axis: usize, outward: f64) -> SurfaceHit {
+        let mut normal = Vec3::zero();
+        match axis {
+            0 => normal.x = outward,
+            1 => normal.y = outward,
+            _ => normal.z = outward,
+        }
+        // The texture coordinates follow the other two axes
+        let (a, b) = match axis {
+            0 => (2, 1),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        let p = r.at(t);
+        let coordinate =
+            |axis: usize| (p[axis] - self.min[axis]) / (self.max[axis] - self.min[axis]);
+        SurfaceHit {
+            t,
+            normal,
+            uv: (coordinate(a), coordinate(b)),
+        }
+    }
+
+    /// The t and axis of the faces where the line of the ray enters and leaves the box
+    fn slabs(&self, r: &Ray) -> Option<((f64, usize), (f64, usize))> {
+        // Find where the ray enters and leaves the slabs of all axes
+        let (mut enter, mut enter_axis) = (f64::NEG_INFINITY, 0);
+        let (mut exit, mut exit_axis) = (f64::INFINITY, 0);
+        for axis in 0..3 {
+            let (origin, dir) = (r.origin[axis], r.dir[axis]);
+            if dir == 0. {
+                if origin < self.min[axis] || origin > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (self.min[axis] - origin) / dir;
+            let t1 = (self.max[axis] - origin) / dir;
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if t0 > enter {
+                enter = t0;
+                enter_axis = axis;
+            }
+            if t1 < exit {
+                exit = t1;
+                exit_axis = axis;
+            }
+        }
+        if enter > exit {
+            return None;
+        }
+        Some(((enter, enter_axis), (exit, exit_axis)))
+    }
+
+    /// The hit where the ray enters, on the face facing against the ray
+    fn enter_hit(&self, r: &Ray, (t, axis): (f64, usize)) -> SurfaceHit {
+        self.face_hit(r, t, axis, -r.dir[axis].signum())
+    }
+
+    /// The hit where the ray leaves, on the face in the direction it travels
+    fn exit_hit(&self, r: &Ray, (t, axis): (f64, usize)) -> SurfaceHit {
+        self.face_hit(r, t, axis, r.dir[axis].signum())
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (enter, exit) = self.slabs(r)?;
+        let hit = if enter.0 > t_min && enter.0 < t_max {
+            self.enter_hit(r, enter)
+        } else if exit.0 > t_min && exit.0 < t_max {
+            self.exit_hit(r, exit)
+        } else {
+            return None;
+        };
+        Some(hit.record(r, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let padding = Vec3::new(FLAT_PADDING, FLAT_PADDING, FLAT_PADDING);
+        Some(Aabb::new(self.min - padding, self.max + padding))
+    }
+}
+
+impl Solid for Cuboid {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        match self.slabs(r) {
+            Some((enter, exit)) if enter.0 < exit.0 => {
+                let hits = [self.enter_hit(r, enter), self.exit_hit(r, exit)];
+                ordered_records(r, hits, &*self.material)
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Hit on a horizontal disk cap of a cylinder or cone, centered on the y axis of the
+/// local coordinates
+fn cap_hit(
+    origin: &Vec3,
+    dir: &Vec3,
+    (t_min, t_max): (f64, f64),
+    y: f64,
+    radius: f64,
+    outward: f64,
+) -> Option<SurfaceHit> {
+    let t = (y - origin.y) / dir.y;
+    if !(t > t_min && t < t_max) {
+        return None;
+    }
+    let (x, z) = (origin.x + t * dir.x, origin.z + t * dir.z);
+    if x * x + z * z > radius * radius {
+        return None;
+    }
+    Some(SurfaceHit {
+        t,
+        normal: Vec3::new(0., outward, 0.),
+        uv: ((x / radius + 1.) / 2., (z / radius + 1.) / 2.),
+    })
+}
+
+/// Angle around the y axis as a texture coordinate in [0, 1]
+fn angle_around_y(x: f64, z: f64) -> f64 {
+    ((-z).atan2(x) + PI) / (2. * PI)
+}
+
+/// A cylinder standing on the xz plane, around a vertical axis
+///
+/// The top and bottom are open, [`Capped`] closes them
+pub struct Cylinder {
+    /// Center of the bottom of the cylinder
+    pub center: Point3,
+    pub radius: f64,
+    pub height: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(center: Point3, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            radius,
+            height,
+            material,
+        }
+    }
+}
+
+impl Cylinder {
+    /// Hits on the side, and on the caps when capped, between t_min and t_max
+    fn surface_hits(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        capped: bool,
+    ) -> [Option<SurfaceHit>; 4] {
+        let (o, d) = (r.origin - self.center, r.dir);
+        let side = |t: f64| {
+            let p = o + t * d;
+            if t > t_min && t < t_max && p.y >= 0. && p.y <= self.height {
+                Some(SurfaceHit {
+                    t,
+                    normal: Vec3::new(p.x, 0., p.z) / self.radius,
+                    uv: (angle_around_y(p.x, p.z), p.y / self.height),
+                })
+            } else {
+                None
+            }
+        };
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2. * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        let (side0, side1) = match quadratic_roots(a, b, c) {
+            Some((t0, t1)) if a != 0. => (side(t0), side(t1)),
+            _ => (None, None),
+        };
+        if !capped {
+            return [side0, side1, None, None];
+        }
+        let range = (t_min, t_max);
+        [
+            side0,
+            side1,
+            cap_hit(&o, &d, range, 0., self.radius, -1.),
+            cap_hit(&o, &d, range, self.height, self.radius, 1.),
+        ]
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hits = self.surface_hits(r, t_min, t_max, false);
+        let hit = IntoIterator::into_iter(hits).fold(None, nearest)?;
+        Some(hit.record(r, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, 0., self.radius);
+        Some(Aabb::new(
+            self.center - extent,
+            self.center + extent + Vec3::new(0., self.height, 0.),
+        ))
+    }
+}
+
+/// A cone standing on the xz plane, around a vertical axis with its apex at the top
+///
+/// The bottom is open, [`Capped`] closes it
+pub struct Cone {
+    /// Center of the bottom of the cone
+    pub center: Point3,
+    /// Radius of the bottom
+    pub radius: f64,
+    pub height: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(center: Point3, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            radius,
+            height,
+            material,
+        }
+    }
+}
+
+impl Cone {
+    /// Hits on the side, and on the bottom when capped, between t_min and t_max
+    fn surface_hits(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        capped: bool,
+    ) -> [Option<SurfaceHit>; 3] {
+        let (o, d) = (r.origin - self.center, r.dir);
+        // The radius shrinks by k for every unit of height
+        let k = self.radius / self.height;
+        let side = |t: f64| {
+            let p = o + t * d;
+            if t > t_min && t < t_max && p.y >= 0. && p.y <= self.height {
+                let distance = (p.x * p.x + p.z * p.z).sqrt();
+                Some(SurfaceHit {
+                    t,
+                    normal: Vec3::new(p.x, k * distance, p.z).unit_vector(),
+                    uv: (angle_around_y(p.x, p.z), p.y / self.height),
+                })
+            } else {
+                None
+            }
+        };
+        // x^2 + z^2 = k^2 (height - y)^2
+        let above = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - k * k * d.y * d.y;
+        let b = 2. * (o.x * d.x + o.z * d.z) + 2. * k * k * above * d.y;
+        let c = o.x * o.x + o.z * o.z - k * k * above * above;
+        let (side0, side1) = match quadratic_roots(a, b, c) {
+            Some((t0, t1)) => (side(t0), side(t1)),
+            None => (None, None),
+        };
+        let bottom = if capped {
+            cap_hit(&o, &d, (t_min, t_max), 0., self.radius, -1.)
+        } else {
+            None
+        };
+        [side0, side1, bottom]
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hits = self.surface_hits(r, t_min, t_max, false);
+        let hit = IntoIterator::into_iter(hits).fold(None, nearest)?;
+        Some(hit.record(r, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, 0., self.radius);
+        Some(Aabb::new(
+            self.center - extent,
+            self.center + extent + Vec3::new(0., self.height, 0.),
+        ))
+    }
+}
+
+/// A flat disk facing in the direction of its normal
+pub struct Disk {
+    pub center: Point3,
+    /// Outward unit normal of the disk
+    pub normal: Vec3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+    /// Direction in which the angular texture coordinate starts
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = tangent_basis(&normal);
+        Self {
+            center,
+            normal,
+            radius,
+            material,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let t = (self.center - r.origin).dot(&self.normal) / self.normal.dot(&r.dir);
+        // Also rejects the NaN of a ray running parallel to the disk
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let offset = r.at(t) - self.center;
+        let distance = offset.length();
+        if distance > self.radius {
+            return None;
+        }
+        // u goes around the center, v from the center to the edge
+        let angle = offset.dot(&self.bitangent).atan2(offset.dot(&self.tangent));
+        let hit = SurfaceHit {
+            t,
+            normal: self.normal,
+            uv: ((angle + PI) / (2. * PI), distance / self.radius),
+        };
+        Some(hit.record(r, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // How far the rim reaches along each axis
+        let n = &self.normal;
+        let extent = |n: f64| self.radius * (1. - n * n).max(0.).sqrt() + FLAT_PADDING;
+        let extent = Vec3::new(extent(n.x), extent(n.y), extent(n.z));
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+/// A torus lying in the xz plane, around a vertical axis
+pub struct Torus {
+    pub center: Point3,
+    /// Distance from the center to the middle of the tube
+    pub major_radius: f64,
+    /// Radius of the tube
+    pub minor_radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl Torus {
+    /// Hits between t_min and t_max, ordered by t
+    fn surface_hits(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec<SurfaceHit> {
+        let (o, d) = (r.origin - self.center, r.dir);
+        let (major, minor) = (self.major_radius, self.minor_radius);
+
+        // Only search where the ray is inside the bounding sphere
+        let outer = major + minor;
+        let (lo, hi) = match quadratic_roots(d.dot(&d), 2. * o.dot(&d), o.dot(&o) - outer * outer) {
+            Some((lo, hi)) => (lo.max(t_min), hi.min(t_max)),
+            None => return Vec::new(),
+        };
+        if lo > hi {
+            return Vec::new();
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) with p = o + t d
+        let a = d.dot(&d);
+        let b = 2. * o.dot(&d);
+        let c = o.dot(&o) + major * major - minor * minor;
+        let e = d.x * d.x + d.z * d.z;
+        let f = 2. * (o.x * d.x + o.z * d.z);
+        let g = o.x * o.x + o.z * o.z;
+        let four_r2 = 4. * major * major;
+        let coefficients = [
+            c * c - four_r2 * g,
+            2. * b * c - four_r2 * f,
+            b * b + 2. * a * c - four_r2 * e,
+            2. * a * b,
+            a * a,
+        ];
+        polynomial_roots(&coefficients, lo, hi)
+            .into_iter()
+            .filter(|&t| t > t_min && t < t_max)
+            .map(|t| {
+                let p = o + t * d;
+                let distance = (p.x * p.x + p.z * p.z).sqrt();
+                // The normal points away from the circle through the middle of the tube
+                let ring = Vec3::new(p.x, 0., p.z) * (major / distance);
+                let tube_angle = p.y.atan2(distance - major);
+                SurfaceHit {
+                    t,
+                    normal: (p - ring).unit_vector(),
+                    uv: (angle_around_y(p.x, p.z), (tube_angle + PI) / (2. * PI)),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hit = self.surface_hits(r, t_min, t_max).into_iter().next()?;
+        Some(hit.record(r, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = Vec3::new(outer, self.minor_radius, outer);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+/// A cylinder or cone with closed ends, which makes it a solid that can be combined
+/// with CSG
+///
+/// An open cylinder or cone has no inside, so only the capped shapes are [`Solid`]
+pub struct Capped<S>(S);
+
+impl Capped<Cylinder> {
+    pub fn cylinder(center: Point3, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Self(Cylinder::new(center, radius, height, material))
+    }
+}
+
+impl Capped<Cone> {
+    pub fn cone(center: Point3, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Self(Cone::new(center, radius, height, material))
+    }
+}
+
+impl Hittable for Capped<Cylinder> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hits = self.0.surface_hits(r, t_min, t_max, true);
+        let hit = IntoIterator::into_iter(hits).fold(None, nearest)?;
+        Some(hit.record(r, &*self.0.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.0.bounding_box()
+    }
+}
+
+impl Hittable for Capped<Cone> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hits = self.0.surface_hits(r, t_min, t_max, true);
+        let hit = IntoIterator::into_iter(hits).fold(None, nearest)?;
+        Some(hit.record(r, &*self.0.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.0.bounding_box()
+    }
+}
+
+impl Solid for Capped<Cylinder> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        let hits = self
+            .0
+            .surface_hits(r, f64::NEG_INFINITY, f64::INFINITY, true);
+        ordered_records(
+            r,
+            IntoIterator::into_iter(hits).flatten(),
+            &*self.0.material,
+        )
+    }
+}
+
+impl Solid for Capped<Cone> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        let hits = self
+            .0
+            .surface_hits(r, f64::NEG_INFINITY, f64::INFINITY, true);
+        ordered_records(
+            r,
+            IntoIterator::into_iter(hits).flatten(),
+            &*self.0.material,
+        )
+    }
+}
+
+impl Solid for Torus {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        let hits = self.surface_hits(r, f64::NEG_INFINITY, f64::INFINITY);
+        ordered_records(r, hits, &*self.material)
+    }
+}
+
+/// `a.x b.y - a.y b.x` for the points a and b, with the exact sign
+///
+/// When the products round to the same value their difference is in their rounding
+/// errors, which are found exactly with a fused multiply-add. Swapping a and b gives
+/// exactly the negated result
+fn edge_function((ax, ay): (f64, f64), (bx, by): (f64, f64)) -> f64 {
+    let (p, q) = (ax * by, ay * bx);
+    if p != q {
+        return p - q;
+    }
+    ax.mul_add(by, -p) - ay.mul_add(bx, -q)
+}
+
+/// Intersect a ray with the triangle (p0, p1, p2) using the watertight algorithm of
+/// Woop, Benthin and Wald
+///
+/// Returns t and the barycentric coordinates (b1, b2) of p1 and p2 at the hit point.
+/// The vertices are moved into a space where the ray starts at the origin and runs
+/// along +z, where the signs of the edge functions tell on which side of each edge the
+/// ray passes. Neighbouring triangles compute the edge function of a shared edge from
+/// the same coordinates with the opposite sign, and the bounds are inclusive, so a ray
+/// through the edge always hits at least one of them
+fn intersect_triangle(
+    r: &Ray,
+    (p0, p1, p2): (&Point3, &Point3, &Point3),
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    // The axis the ray runs along the most becomes z
+    let d = r.dir;
+    let kz = if d.x.abs() > d.y.abs() && d.x.abs() > d.z.abs() {
+        0
+    } else if d.y.abs() > d.z.abs() {
+        1
+    } else {
+        2
+    };
+    let (kx, ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if d[kz] == 0. {
+        return None;
+    }
+    // Shear the ray onto the z axis, with z scaled to the t of the ray
+    let (sx, sy, sz) = (d[kx] / d[kz], d[ky] / d[kz], 1. / d[kz]);
+    let [(a, az), (b, bz), (c, cz)] = [p0, p1, p2].map(|p| {
+        let p = p - r.origin;
+        ((p[kx] - sx * p[kz], p[ky] - sy * p[kz]), sz * p[kz])
+    });
+
+    // Twice the signed areas of the triangles between the ray and each edge, which
+    // weigh the vertex opposite of the edge
+    let e0 = edge_function(b, c);
+    let e1 = edge_function(c, a);
+    let e2 = edge_function(a, b);
+    if (e0 < 0. || e1 < 0. || e2 < 0.) && (e0 > 0. || e1 > 0. || e2 > 0.) {
+        return None;
+    }
+    let determinant = e0 + e1 + e2;
+    // The ray runs parallel to the triangle, or the triangle is degenerate
+    if determinant == 0. || !determinant.is_finite() {
+        return None;
+    }
+
+    let t = (e0 * az + e1 * bz + e2 * cz) / determinant;
+    if !(t > t_min && t < t_max) {
+        return None;
+    }
+    Some((t, e1 / determinant, e2 / determinant))
+}
+
+/// A single triangle, optionally with per-vertex normals and texture coordinates
+///
+/// The front of the triangle is the side from which the vertices are ordered
+/// counter-clockwise
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    /// Normals used for smooth shading
+    pub normals: Option<[Vec3; 3]>,
+    /// Texture coordinates, the barycentric coordinates are used when these are missing
+    pub uvs: Option<[(f64, f64); 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Point3; 3], material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices,
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    /// Set the per-vertex normals
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Set the per-vertex texture coordinates
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+/// Optional per-vertex attributes of a triangle, interpolated over its surface
+#[derive(Default)]
+pub(crate) struct VertexAttributes<'a> {
+    pub normals: Option<[&'a Vec3; 3]>,
+    pub uvs: Option<[(f64, f64); 3]>,
+    pub colors: Option<[&'a Color; 3]>,
+}
+
+/// Hit a triangle, interpolating the vertex attributes that are given
+///
+/// Shared by [`Triangle`] and the triangles of a [`TriangleMesh`](crate::mesh::TriangleMesh)
+pub(crate) fn hit_triangle<'a>(
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+    [p0, p1, p2]: [&Point3; 3],
+    attributes: &VertexAttributes,
+    material: &'a dyn Material,
+) -> Option<HitRecord<'a>> {
+    let (t, b1, b2) = intersect_triangle(r, (p0, p1, p2), t_min, t_max)?;
+    let b0 = 1. - b1 - b2;
+
+    let geometric_normal = (p1 - p0).cross(&(p2 - p0)).unit_vector();
+    let (u, v) = match attributes.uvs {
+        Some([uv0, uv1, uv2]) => (
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        ),
+        None => (b1, b2),
+    };
+    let mut record = HitRecord::new(r, r.at(t), &geometric_normal, t, material).with_uv(u, v);
+    if let Some([n0, n1, n2]) = attributes.normals {
+        record = record.with_shading_normal(&(b0 * n0 + b1 * n1 + b2 * n2).unit_vector());
+    }
+    if let Some([c0, c1, c2]) = attributes.colors {
+        record = record.with_vertex_color(b0 * c0 + b1 * c1 + b2 * c2);
+    }
+    Some(record)
+}
+
+/// Bounding box of a triangle
+pub(crate) fn triangle_bounding_box([p0, p1, p2]: [&Point3; 3]) -> Aabb {
+    // Give triangles in an axis-aligned plane some thickness
+    let padding = Vec3::new(FLAT_PADDING, FLAT_PADDING, FLAT_PADDING);
+    Aabb::new(p0.min(p1).min(p2) - padding, p0.max(p1).max(p2) + padding)
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let [p0, p1, p2] = &self.vertices;
+        let attributes = VertexAttributes {
+            normals: self.normals.as_ref().map(|[n0, n1, n2]| [n0, n1, n2]),
+            uvs: self.uvs,
+            colors: None,
+        };
+        hit_triangle(r, t_min, t_max, [p0, p1, p2], &attributes, &*self.material)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [p0, p1, p2] = &self.vertices;
+        Some(triangle_bounding_box([p0, p1, p2]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::gray;
+    use crate::sampler::Sampler;
+    use rand::Rng;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            [
+                Point3::new(0., 0., 0.),
+                Point3::new(1., 0., 0.),
+                Point3::new(0., 1., 0.),
+            ],
+            gray(),
+        )
+    }
+
+    /// A ray that goes straight down the z axis through (x, y)
+    fn ray_through(x: f64, y: f64) -> Ray {
+        Ray::new(Point3::new(x, y, 1.), Vec3::new(0., 0., -1.))
+    }
+
+    #[test]
+    fn hits_interior() {
+        let triangle = triangle();
+        let hit = triangle
+            .hit(&ray_through(0.25, 0.25), 0., f64::INFINITY)
+            .unwrap();
+        assert!((hit.t - 1.).abs() < 1e-12);
+        assert!((hit.p - Point3::new(0.25, 0.25, 0.)).length() < 1e-12);
+        assert!((hit.u - 0.25).abs() < 1e-12 && (hit.v - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn hits_edges() {
+        let triangle = triangle();
+        for (x, y) in [(0.5, 0.), (0., 0.5), (0.5, 0.5), (0.25, 0.75)].iter() {
+            assert!(
+                triangle
+                    .hit(&ray_through(*x, *y), 0., f64::INFINITY)
+                    .is_some(),
+                "missed edge point ({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn hits_vertices() {
+        let triangle = triangle();
+        for vertex in triangle.vertices.iter() {
+            let hit = triangle.hit(&ray_through(vertex.x, vertex.y), 0., f64::INFINITY);
+            assert!(hit.is_some(), "missed vertex {:?}", vertex);
+        }
+    }
+
+    #[test]
+    fn misses_outside() {
+        let triangle = triangle();
+        for (x, y) in [(-1e-9, 0.5), (0.5, -1e-9), (0.5 + 1e-9, 0.5), (1.1, 1.1)].iter() {
+            assert!(triangle
+                .hit(&ray_through(*x, *y), 0., f64::INFINITY)
+                .is_none());
+        }
+        // Parallel to the triangle
+        let parallel = Ray::new(Point3::new(-1., 0.25, 0.), Vec3::new(1., 0., 0.));
+        assert!(triangle.hit(&parallel, 0., f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn shared_edge_is_watertight() {
+        // Two triangles sharing the diagonal from (1, 0) to (0, 1)
+        let first = triangle();
+        let second = Triangle::new(
+            [
+                Point3::new(1., 0., 0.),
+                Point3::new(1., 1., 0.),
+                Point3::new(0., 1., 0.),
+            ],
+            first.material.clone(),
+        );
+        for i in 0..=100 {
+            let x = i as f64 / 100.;
+            let ray = ray_through(x, 1. - x);
+            assert!(
+                first.hit(&ray, 0., f64::INFINITY).is_some()
+                    || second.hit(&ray, 0., f64::INFINITY).is_some(),
+                "ray through ({}, {}) fell through the shared edge",
+                x,
+                1. - x
+            );
+        }
+    }
+
+    #[test]
+    fn front_face_follows_winding() {
+        let triangle = triangle();
+        let front = triangle
+            .hit(&ray_through(0.25, 0.25), 0., f64::INFINITY)
+            .unwrap();
+        assert!(front.front_face);
+        assert!(front.normal.z > 0.);
+
+        let from_below = Ray::new(Point3::new(0.25, 0.25, -1.), Vec3::new(0., 0., 1.));
+        let back = triangle.hit(&from_below, 0., f64::INFINITY).unwrap();
+        assert!(!back.front_face);
+        assert!(back.normal.z < 0.);
+    }
+
+    #[test]
+    fn interpolates_vertex_normals() {
+        let tilted = Vec3::new(1., 0., 1.).unit_vector();
+        let triangle = triangle().with_normals([tilted, tilted, tilted]);
+        let from_below = Ray::new(Point3::new(0.25, 0.25, -1.), Vec3::new(0., 0., 1.));
+        let hit = triangle.hit(&from_below, 0., f64::INFINITY).unwrap();
+        // The shading normal is flipped together with the geometric normal
+        assert!(!hit.front_face);
+        assert!((hit.normal + tilted).length() < 1e-12);
+    }
+
+    #[test]
+    fn shared_edge_is_watertight_for_any_ray() {
+        // Two triangles of a tilted quad, split along the diagonal from a to b. None of
+        // the coordinates can be represented exactly
+        let (a, b) = (Point3::new(0.1, 0.7, 0.3), Point3::new(0.7, 0.1, 0.9));
+        let first = Triangle::new([a, b, Point3::new(0.7, 0.7, 0.6)], gray());
+        let second = Triangle::new([b, a, Point3::new(0.1, 0.1, 0.6)], gray());
+        let mut sampler = Sampler::from_seed(3);
+        for _ in 0..100_000 {
+            let target = a + sampler.gen::<f64>() * (b - a);
+            let origin = target + 3. * Vec3::random_unit_vector(&mut sampler);
+            let ray = Ray::new(origin, sampler.gen_range(0.1, 10.) * (target - origin));
+            assert!(
+                first.hit(&ray, 0., f64::INFINITY).is_some()
+                    || second.hit(&ray, 0., f64::INFINITY).is_some(),
+                "ray from {:?} through {:?} fell through the shared edge",
+                origin,
+                target
+            );
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn finds_polynomial_roots() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        let roots = polynomial_roots(&[24., -50., 35., -10., 1.], 0., 10.);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([1., 2., 3., 4.].iter()) {
+            assert_close(*root, *expected);
+        }
+        assert_eq!(
+            polynomial_roots(&[24., -50., 35., -10., 1.], 1.5, 2.5).len(),
+            1
+        );
+        assert!(polynomial_roots(&[1., 0., 1.], -10., 10.).is_empty());
+    }
+
+    #[test]
+    fn cuboid_hits_outside_and_inside() {
+        let cuboid = Cuboid::new(Point3::new(1., 1., 1.), Point3::new(-1., -1., -1.), gray());
+        let ray = Ray::new(Point3::new(0.5, 0.5, 5.), Vec3::new(0., 0., -1.));
+        let hit = cuboid.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.t, 4.);
+        assert!(hit.front_face && hit.normal.z == 1.);
+        assert_close(hit.u, 0.75);
+
+        let inside = Ray::new(Point3::zero(), Vec3::new(1., 0., 0.));
+        let hit = cuboid.hit(&inside, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.t, 1.);
+        assert!(!hit.front_face && hit.normal.x == -1.);
+    }
+
+    #[test]
+    fn cylinder_caps() {
+        let from_above = Ray::new(Point3::new(0.2, 5., 0.), Vec3::new(0., -1., 0.));
+        let capped = Capped::cylinder(Point3::zero(), 1., 2., gray());
+        let hit = capped.hit(&from_above, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.t, 3.);
+        assert!(hit.normal.y == 1.);
+
+        // Without caps the ray passes straight through
+        let open = Cylinder::new(Point3::zero(), 1., 2., gray());
+        assert!(open.hit(&from_above, 0.001, f64::INFINITY).is_none());
+
+        let from_side = Ray::new(Point3::new(5., 1., 0.), Vec3::new(-1., 0., 0.));
+        let hit = open.hit(&from_side, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.t, 4.);
+        assert_close(hit.v, 0.5);
+    }
+
+    #[test]
+    fn cone_narrows_to_apex() {
+        let cone = Capped::cone(Point3::zero(), 1., 2., gray());
+        // Halfway up the radius is 0.5
+        let ray = Ray::new(Point3::new(5., 1., 0.), Vec3::new(-1., 0., 0.));
+        let hit = cone.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.t, 4.5);
+        assert!(hit.normal.x > 0. && hit.normal.y > 0.);
+        assert_close(hit.normal.length(), 1.);
+
+        let above_apex = Ray::new(Point3::new(5., 2.1, 0.), Vec3::new(-1., 0., 0.));
+        assert!(cone.hit(&above_apex, 0.001, f64::INFINITY).is_none());
+        let from_below = Ray::new(Point3::new(0.9, -1., 0.), Vec3::new(0., 1., 0.));
+        assert_close(cone.hit(&from_below, 0.001, f64::INFINITY).unwrap().t, 1.);
+    }
+
+    #[test]
+    fn disk_is_bounded_by_radius() {
+        let disk = Disk::new(Point3::zero(), Vec3::new(0., 0., 2.), 1., gray());
+        let inside = Ray::new(Point3::new(0.5, 0.5, 1.), Vec3::new(0., 0., -1.));
+        assert_close(disk.hit(&inside, 0.001, f64::INFINITY).unwrap().t, 1.);
+        let outside = Ray::new(Point3::new(0.8, 0.8, 1.), Vec3::new(0., 0., -1.));
+        assert!(disk.hit(&outside, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn torus_hits_tube_and_misses_hole() {
+        let torus = Torus::new(Point3::zero(), 2., 0.5, gray());
+        let down_through_tube = Ray::new(Point3::new(2., 5., 0.), Vec3::new(0., -2., 0.));
+        let hit = torus.hit(&down_through_tube, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.t, 2.25);
+        assert_close(hit.normal.y, 1.);
+
+        let through_hole = Ray::new(Point3::new(0., 5., 0.), Vec3::new(0., -1., 0.));
+        assert!(torus.hit(&through_hole, 0.001, f64::INFINITY).is_none());
+
+        // Along the x axis the ray crosses the tube twice on each side
+        let across = Ray::new(Point3::new(-5., 0., 0.), Vec3::new(1., 0., 0.));
+        let hit = torus.hit(&across, 0.001, f64::INFINITY).unwrap();
+        assert_close(hit.t, 2.5);
+        let hit = torus.hit(&across, 2.6, f64::INFINITY).unwrap();
+        assert_close(hit.t, 3.5);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn plane_faces_both_sides() {
+        let plane = Plane::new(Point3::zero(), Vec3::new(0., 2., 0.), gray());
+        let down = Ray::new(Point3::new(2., 1., 3.), Vec3::new(0., -1., 0.));
+        let hit = plane.hit(&down, 0., f64::INFINITY).unwrap();
+        assert!((hit.t - 1.).abs() < 1e-12);
+        assert!(hit.front_face);
+        assert!((hit.normal - Vec3::new(0., 1., 0.)).length() < 1e-12);
+        // The texture coordinates are distances along the plane
+        assert!((hit.u - 3.).abs() < 1e-12 && (hit.v - 2.).abs() < 1e-12);
+
+        let up = Ray::new(Point3::new(2., -1., 3.), Vec3::new(0., 1., 0.));
+        let hit = plane.hit(&up, 0., f64::INFINITY).unwrap();
+        assert!(!hit.front_face);
+        assert!((hit.normal - Vec3::new(0., -1., 0.)).length() < 1e-12);
+
+        let parallel = Ray::new(Point3::new(0., 1., 0.), Vec3::new(1., 0., 0.));
+        assert!(plane.hit(&parallel, 0., f64::INFINITY).is_none());
+        assert!(plane.hit(&down, 0., 0.5).is_none());
+        assert!(plane.bounding_box().is_none());
+    }
+
+    #[test]
+    fn rects_face_positive_axis() {
+        // The same rectangle in each of the planes, hit from both sides
+        let xy = XyRect::new(0., 2., 1., 3., -1., gray());
+        let xz = XzRect::new(0., 2., 1., 3., -1., gray());
+        let yz = YzRect::new(0., 2., 1., 3., -1., gray());
+        let rects: [(&dyn Hittable, (usize, usize, usize)); 3] =
+            [(&xy, (0, 1, 2)), (&xz, (0, 2, 1)), (&yz, (1, 2, 0))];
+        for (rect, (a, b, k)) in rects {
+            // A point with the given coordinates along the axes of the rectangle
+            let at = |along_a: f64, along_b: f64, along_k: f64| {
+                let mut p = [0.; 3];
+                p[a] = along_a;
+                p[b] = along_b;
+                p[k] = along_k;
+                Point3::from(p)
+            };
+            let (dir, normal) = (at(0., 0., -1.), at(0., 0., 1.));
+
+            let above = Ray::new(at(0.5, 2., 1.), dir);
+            let hit = rect.hit(&above, 0., f64::INFINITY).unwrap();
+            assert!((hit.t - 2.).abs() < 1e-12);
+            assert!(hit.front_face);
+            assert!((hit.normal - normal).length() < 1e-12);
+            assert!((hit.u - 0.25).abs() < 1e-12 && (hit.v - 0.5).abs() < 1e-12);
+
+            let below = Ray::new(at(0.5, 2., -3.), -dir);
+            let hit = rect.hit(&below, 0., f64::INFINITY).unwrap();
+            assert!(!hit.front_face);
+            assert!((hit.normal + normal).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn rects_include_edges() {
+        let rect = XyRect::new(0., 2., 1., 3., -1., gray());
+        let hits = |x: f64, y: f64| rect.hit(&ray_through(x, y), 0., f64::INFINITY).is_some();
+        for (x, y) in [(0., 2.), (2., 2.), (1., 1.), (1., 3.), (2., 3.)] {
+            assert!(hits(x, y), "missed edge point ({}, {})", x, y);
+        }
+        for (x, y) in [
+            (-1e-9, 2.),
+            (2. + 1e-9, 2.),
+            (1., 1. - 1e-9),
+            (1., 3. + 1e-9),
+        ] {
+            assert!(!hits(x, y), "hit outside point ({}, {})", x, y);
+        }
+        let hit = rect.hit(&ray_through(2., 3.), 0., f64::INFINITY).unwrap();
+        assert!((hit.u - 1.).abs() < 1e-12 && (hit.v - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rect_boxes_are_padded() {
+        let xy = XyRect::new(0., 2., 1., 3., -1., gray())
+            .bounding_box()
+            .unwrap();
+        assert_eq!([xy.min.x, xy.min.y, xy.min.z], [0., 1., -1. - FLAT_PADDING]);
+        assert_eq!([xy.max.x, xy.max.y, xy.max.z], [2., 3., -1. + FLAT_PADDING]);
+        let xz = XzRect::new(0., 2., 1., 3., -1., gray())
+            .bounding_box()
+            .unwrap();
+        assert_eq!([xz.min.x, xz.min.y, xz.min.z], [0., -1. - FLAT_PADDING, 1.]);
+        assert_eq!([xz.max.x, xz.max.y, xz.max.z], [2., -1. + FLAT_PADDING, 3.]);
+        let yz = YzRect::new(0., 2., 1., 3., -1., gray())
+            .bounding_box()
+            .unwrap();
+        assert_eq!([yz.min.x, yz.min.y, yz.min.z], [-1. - FLAT_PADDING, 0., 1.]);
+        assert_eq!([yz.max.x, yz.max.y, yz.max.z], [-1. + FLAT_PADDING, 2., 3.]);
+        // A ray grazing the rectangle still enters its box
+        let grazing = Ray::new(Point3::new(-1., 2., -1.), Vec3::new(1., 0., 0.));
+        assert!(xy.hit(&grazing, 0., f64::INFINITY));
+    }
 }
@@ -0,0 +1,375 @@
+use crate::camera::CameraBuilder;
+use crate::color::{self, Color};
+use crate::load::LoadError;
+use crate::material::{Dialectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::matrix::Mat4;
+use crate::mesh::{self, MeshData, TriangleMesh};
+use crate::texture::ImageTexture;
+use crate::vec3::{Point3, Vec3};
+use std::convert::TryInto;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The meshes and camera of a glTF scene
+pub struct GltfScene {
+    /// A mesh for every primitive of every mesh node, with the node transform applied
+    pub meshes: Vec<TriangleMesh>,
+    /// The first perspective camera in the scene
+    pub camera: Option<CameraBuilder>,
+    /// Aspect ratio of the camera, if the file specifies one
+    pub aspect_ratio: Option<f64>,
+}
+
+/// Convert the pixels of a glTF image to a texture with linear colors, multiplied by a
+/// factor
+///
+/// Integer images are sRGB encoded, as glTF requires for base color textures
+fn image_texture(image: &::gltf::image::Data, factor: &Color) -> ImageTexture {
+    use ::gltf::image::Format;
+    let (channels, size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let value = |bytes: &[u8]| match size {
+        1 => color::srgb_to_linear(bytes[0] as f64 / u8::MAX as f64),
+        2 => color::srgb_to_linear(
+            u16::from_ne_bytes(bytes.try_into().unwrap()) as f64 / u16::MAX as f64,
+        ),
+        _ => f32::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+    };
+    let pixels = image
+        .pixels
+        .chunks_exact(channels * size)
+        .map(|pixel| {
+            let channel = |i: usize| value(&pixel[i * size..(i + 1) * size]);
+            let color = if channels < 3 {
+                // Grayscale, possibly with alpha
+                let gray = channel(0);
+                Color::new(gray, gray, gray)
+            } else {
+                Color::new(channel(0), channel(1), channel(2))
+            };
+            color * *factor
+        })
+        .collect();
+    ImageTexture::new(image.width as usize, image.height as usize, pixels)
+}
+
+/// Convert a PBR metallic-roughness material to the closest matching material
+///
+/// Emissive materials become a [`DiffuseLight`], transmissive materials a
+/// [`Dialectric`], mostly metallic materials a [`Metal`] with the roughness as fuzz,
+/// and everything else a [`Lambertian`] with the base color and texture
+fn convert_material(
+    material: &::gltf::Material,
+    images: &[::gltf::image::Data],
+) -> Arc<dyn Material> {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, _alpha] = pbr.base_color_factor();
+    let base_color = Color::new(r as f64, g as f64, b as f64);
+
+    let [r, g, b] = material.emissive_factor();
+    let strength = material.emissive_strength().unwrap_or(1.) as f64;
+    let emission = Color::new(r as f64, g as f64, b as f64) * strength;
+    if emission.length_squared() > 0. {
+        return match material.emissive_texture() {
+            Some(info) => {
+                let image = &images[info.texture().source().index()];
+                Arc::new(DiffuseLight::textured(Arc::new(image_texture(
+                    image, &emission,
+                ))))
+            }
+            None => Arc::new(DiffuseLight::new(emission)),
+        };
+    }
+
+    let transmission = material
+        .transmission()
+        .map(|transmission| transmission.transmission_factor())
+        .unwrap_or(0.);
+    if transmission >= 0.5 {
+        Arc::new(Dialectric::new(material.ior().unwrap_or(1.5) as f64))
+    } else if pbr.metallic_factor() >= 0.5 {
+        Arc::new(Metal::new(base_color, pbr.roughness_factor() as f64))
+    } else {
+        match pbr.base_color_texture() {
+            Some(info) => {
+                let image = &images[info.texture().source().index()];
+                Arc::new(Lambertian::textured(Arc::new(image_texture(
+                    image,
+                    &base_color,
+                ))))
+            }
+            None => Arc::new(Lambertian::new(base_color)),
+        }
+    }
+}
+
+/// State while walking the node hierarchy
+struct Importer<'a> {
+    buffers: &'a [::gltf::buffer::Data],
+    materials: Vec<Arc<dyn Material>>,
+    /// Material for every primitive instead of the materials in the file
+    material: Option<Arc<dyn Material>>,
+    scene: GltfScene,
+}
+
+impl Importer<'_> {
+    fn visit(&mut self, node: ::gltf::Node, parent: &Mat4) {
+        // glTF stores matrices column by column
+        let local = node.transform().matrix();
+        let local = Mat4::new([0, 1, 2, 3].map(|r| [0, 1, 2, 3].map(|c| local[c][r] as f64)));
+        let transform = *parent * local;
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(&primitive, &transform);
+            }
+        }
+        if let Some(camera) = node.camera() {
+            self.add_camera(&camera, &transform);
+        }
+        for child in node.children() {
+            self.visit(child, &transform);
+        }
+    }
+
+    fn add_primitive(&mut self, primitive: &::gltf::Primitive, transform: &Mat4) {
+        if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+            return;
+        }
+        // A transform that flattens the mesh leaves nothing to see
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let positions: Vec<Point3> = match reader.read_positions() {
+            Some(positions) => positions
+                .map(|p| {
+                    transform.transform_point(&Point3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+                })
+                .collect(),
+            None => return,
+        };
+        let vertex_count = positions.len();
+
+        let texture_set = primitive
+            .material()
+            .pbr_metallic_roughness()
+            .base_color_texture()
+            .map(|info| info.tex_coord())
+            .unwrap_or(0);
+        let mut mesh = MeshData {
+            positions,
+            normals: reader
+                .read_normals()
+                .map(|normals| {
+                    normals
+                        .map(|n| {
+                            inverse
+                                .transform_normal(&Vec3::new(n[0] as f64, n[1] as f64, n[2] as f64))
+                                .unit_vector()
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            // glTF puts v = 0 at the top of the image
+            uvs: reader
+                .read_tex_coords(texture_set)
+                .map(|uvs| {
+                    uvs.into_f32()
+                        .map(|uv| (uv[0] as f64, 1. - uv[1] as f64))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            colors: reader
+                .read_colors(0)
+                .map(|colors| {
+                    colors
+                        .into_rgb_f32()
+                        .map(|c| Color::new(c[0] as f64, c[1] as f64, c[2] as f64))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            triangles: match reader.read_indices() {
+                Some(indices) => {
+                    let indices: Vec<u32> = indices.into_u32().collect();
+                    indices
+                        .chunks_exact(3)
+                        .map(|t| [t[0], t[1], t[2]])
+                        .collect()
+                }
+                None => (0..vertex_count as u32 / 3)
+                    .map(|i| [3 * i, 3 * i + 1, 3 * i + 2])
+                    .collect(),
+            },
+        };
+        if mesh.normals.len() != vertex_count {
+            mesh.normals.clear();
+        }
+        if mesh.uvs.len() != vertex_count {
+            mesh.uvs.clear();
+        }
+        if mesh.colors.len() != vertex_count {
+            mesh.colors.clear();
+        }
+        // A mirroring transform turns the triangles inside out
+        if transform.determinant3() < 0. {
+            for triangle in mesh.triangles.iter_mut() {
+                triangle.swap(1, 2);
+            }
+        }
+
+        let material = match (&self.material, primitive.material().index()) {
+            (Some(material), _) => material.clone(),
+            (None, Some(index)) => self.materials[index].clone(),
+            (None, None) => mesh::default_material(&mesh),
+        };
+        // Primitives with indices of vertices that do not exist are skipped
+        if let Ok(mesh) = TriangleMesh::new(Arc::new(mesh), material) {
+            self.scene.meshes.push(mesh);
+        }
+    }
+
+    fn add_camera(&mut self, camera: &::gltf::Camera, transform: &Mat4) {
+        let perspective = match camera.projection() {
+            ::gltf::camera::Projection::Perspective(perspective) => perspective,
+            ::gltf::camera::Projection::Orthographic(_) => return,
+        };
+        if self.scene.camera.is_some() {
+            return;
+        }
+        // Cameras look along -z with +y up
+        let look_from = transform.transform_point(&Point3::zero());
+        let look_at = transform.transform_point(&Point3::new(0., 0., -1.));
+        let mut builder = CameraBuilder::new(look_from, look_at);
+        builder.set_up_vector(&transform.transform_vector(&Vec3::new(0., 1., 0.)));
+        builder.set_vfov((perspective.yfov() as f64).to_degrees());
+        builder.set_aperture(0.);
+        self.scene.camera = Some(builder);
+        self.scene.aspect_ratio = perspective.aspect_ratio().map(|ratio| ratio as f64);
+    }
+}
+
+/// Load the default scene from a glTF 2.0 file (.gltf or .glb)
+///
+/// All mesh nodes are flattened into world space meshes. Materials are converted with
+/// their base color textures, unless a material is given, which is then used for all
+/// meshes. The first perspective camera becomes the camera of the scene
+pub fn load_gltf(
+    path: impl AsRef<Path>,
+    material: Option<Arc<dyn Material>>,
+) -> Result<GltfScene, LoadError> {
+    let path = path.as_ref();
+    let (document, buffers, images) = ::gltf::import(path).map_err(|e| LoadError {
+        path: Some(path.to_path_buf()),
+        message: e.to_string(),
+    })?;
+
+    let materials = match &material {
+        Some(_) => Vec::new(),
+        None => document
+            .materials()
+            .map(|material| convert_material(&material, &images))
+            .collect(),
+    };
+    let mut importer = Importer {
+        buffers: &buffers,
+        materials,
+        material,
+        scene: GltfScene {
+            meshes: Vec::new(),
+            camera: None,
+            aspect_ratio: None,
+        },
+    };
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes() {
+            importer.visit(node, &Mat4::identity());
+        }
+    }
+    Ok(importer.scene)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::{response, Response};
+    use crate::sampler::Sampler;
+    use std::path::PathBuf;
+
+    fn quad() -> GltfScene {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("scenes/textured_quad.gltf");
+        load_gltf(path, None).unwrap()
+    }
+
+    #[test]
+    fn composes_node_transforms() {
+        let scene = quad();
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = scene.meshes[0].data();
+        // The quad is scaled and raised by its node, then turned to face +x and moved
+        // by its parent
+        let expected = [
+            Point3::new(1., 0., 1.),
+            Point3::new(1., 0., -1.),
+            Point3::new(1., 2., -1.),
+            Point3::new(1., 2., 1.),
+        ];
+        for (position, expected) in mesh.positions.iter().zip(&expected) {
+            assert!((position - expected).length() < 1e-6, "{:?}", position);
+        }
+        assert_eq!(mesh.triangles, [[0, 1, 2], [0, 2, 3]]);
+        // The texture coordinates are flipped to put v = 0 at the bottom
+        assert_eq!(mesh.uvs[0], (0., 0.));
+        assert_eq!(mesh.uvs[2], (1., 1.));
+    }
+
+    #[test]
+    fn imports_camera() {
+        let scene = quad();
+        assert_eq!(scene.aspect_ratio, Some(2.));
+        let mut builder = scene.camera.unwrap();
+        builder.set_aspect_ratio(2.);
+        let camera = builder.build();
+        let mut sampler = Sampler::from_seed(0);
+
+        // The camera looks along -x at the center of the quad
+        let center = camera.get_ray(0.5, 0.5, &mut sampler);
+        assert!((center.origin - Point3::new(6., 1., 0.)).length() < 1e-6);
+        assert!((center.dir.unit_vector() - Vec3::new(-1., 0., 0.)).length() < 1e-6);
+        // Up is +y, and the top of the image is half the field of view above the center
+        let top = camera.get_ray(0.5, 1., &mut sampler).dir;
+        assert!(top.y > 0. && top.z.abs() < 1e-6);
+        assert!(((top.y / -top.x).atan() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn converts_textured_material() {
+        let scene = quad();
+        match response(&**scene.meshes[0].material()) {
+            // The texture is sRGB encoded
+            Response::Diffuse { albedo } => {
+                let expected = Color::new(
+                    color::srgb_to_linear(128. / 255.),
+                    color::srgb_to_linear(64. / 255.),
+                    1.,
+                );
+                assert!((albedo - expected).length() < 1e-6, "{:?}", albedo);
+            }
+            other => panic!("expected a diffuse quad, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::aabb::Aabb;
+use crate::color;
+use crate::hittable::{HitRecord, Hittable};
+use crate::load::{self, LoadError};
+use crate::material::Material;
+use crate::mesh::{MeshData, TriangleMesh};
+use crate::ray::Ray;
+use crate::texture;
+use crate::vec3::Vec3;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Terrain given by a grid of heights
+///
+/// The grid is spread over the x and z extent of the bounds, and heights from 0 to 1
+/// are mapped to the y extent. Every cell is split into two triangles with normals
+/// from the slope of the grid, so the terrain is smoothly shaded
+pub struct Heightfield {
+    mesh: TriangleMesh,
+}
+
+impl Heightfield {
+    /// Create a heightfield from `width * depth` heights, row by row from -z to +z
+    ///
+    /// Fails when there are fewer than 2 heights in either direction or the number of
+    /// heights does not match
+    pub fn new(
+        width: usize,
+        depth: usize,
+        heights: &[f64],
+        bounds: Aabb,
+        material: Arc<dyn Material>,
+    ) -> Result<Self, LoadError> {
+        let error = |message: &str| {
+            Err(LoadError {
+                path: None,
+                message: message.into(),
+            })
+        };
+        if width < 2 || depth < 2 {
+            return error("heightfield needs at least 2x2 heights");
+        }
+        if heights.len() != width * depth {
+            return error("wrong number of heights");
+        }
+
+        let size = bounds.max - bounds.min;
+        let (dx, dz) = (size.x / (width - 1) as f64, size.z / (depth - 1) as f64);
+        let height = |column: usize, row: usize| heights[row * width + column] * size.y;
+
+        let mut mesh = MeshData::default();
+        for row in 0..depth {
+            for column in 0..width {
+                mesh.positions.push(
+                    bounds.min
+                        + Vec3::new(column as f64 * dx, height(column, row), row as f64 * dz),
+                );
+                // The slope from the neighbours, one sided at the edges
+                let (left, right) = (column.saturating_sub(1), (column + 1).min(width - 1));
+                let (back, front) = (row.saturating_sub(1), (row + 1).min(depth - 1));
+                let slope_x =
+                    (height(right, row) - height(left, row)) / ((right - left) as f64 * dx);
+                let slope_z =
+                    (height(column, front) - height(column, back)) / ((front - back) as f64 * dz);
+                mesh.normals
+                    .push(Vec3::new(-slope_x, 1., -slope_z).unit_vector());
+                // The image is seen from above with its top at -z
+                mesh.uvs.push((
+                    column as f64 / (width - 1) as f64,
+                    1. - row as f64 / (depth - 1) as f64,
+                ));
+            }
+        }
+        let index = |column: usize, row: usize| (row * width + column) as u32;
+        for row in 0..depth - 1 {
+            for column in 0..width - 1 {
+                let (a, b) = (index(column, row), index(column + 1, row));
+                let (c, d) = (index(column, row + 1), index(column + 1, row + 1));
+                // Counter-clockwise seen from above
+                mesh.triangles.push([a, c, b]);
+                mesh.triangles.push([b, c, d]);
+            }
+        }
+        Ok(Self {
+            mesh: TriangleMesh::new(Arc::new(mesh), material)?,
+        })
+    }
+
+    /// Load the heights from a grayscale PNG image, black is the bottom of the bounds
+    /// and white the top
+    ///
+    /// The top of the image is at -z. Color images are converted to their luminance
+    pub fn load(
+        path: impl AsRef<Path>,
+        bounds: Aabb,
+        material: Arc<dyn Material>,
+    ) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let error = |message: String| LoadError {
+            path: Some(path.to_path_buf()),
+            message,
+        };
+        let data = load::read_file(path)?;
+        let (width, depth, pixels) = texture::decode_png(&data).map_err(error)?;
+        let heights: Vec<f64> = pixels.iter().map(color::luminance).collect();
+        if width < 2 || depth < 2 {
+            return Err(error(
+                "heightfield image must be at least 2x2 pixels".into(),
+            ));
+        }
+        Self::new(width, depth, &heights, bounds, material).map_err(|e| e.in_file(path))
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.mesh.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.mesh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::gray;
+    use crate::vec3::Point3;
+
+    #[test]
+    fn follows_heights() {
+        // A ramp rising towards +x
+        let heights = [0., 0.5, 1., 0., 0.5, 1.];
+        let bounds = Aabb::new(Point3::new(0., 0., 0.), Point3::new(2., 2., 1.));
+        let material = gray();
+        let terrain = Heightfield::new(3, 2, &heights, bounds, material).unwrap();
+
+        let down = |x: f64| Ray::new(Point3::new(x, 5., 0.5), Vec3::new(0., -1., 0.));
+        let hit = terrain.hit(&down(0.5), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.p.y - 0.5).abs() < 1e-12);
+        assert!(hit.front_face);
+        let expected = Vec3::new(-1., 1., 0.).unit_vector();
+        assert!((hit.normal - expected).length() < 1e-12);
+
+        let hit = terrain.hit(&down(1.5), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.p.y - 1.5).abs() < 1e-12);
+        assert!(terrain.hit(&down(2.5), 0.001, f64::INFINITY).is_none());
+    }
+}
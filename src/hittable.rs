@@ -1,5 +1,8 @@
+use crate::aabb::Aabb;
+use crate::color::Color;
 use crate::vec3::{dot, Point3};
 use crate::{material::Material, ray::Ray, vec3::Vec3};
+use std::sync::Arc;
 
 pub struct HitRecord<'a> {
     /// Point where the ray hit
@@ -8,14 +11,32 @@ pub struct HitRecord<'a> {
     pub normal: Vec3,
     /// The t values located on the ray
     pub t: f64,
+    /// Horizontal texture coordinate of the hit point
+    pub u: f64,
+    /// Vertical texture coordinate of the hit point
+    pub v: f64,
     /// If the ray is fromt facing
     pub front_face: bool,
+    /// Color of the surface given by the geometry, e.g. the vertex colors of a mesh
+    pub vertex_color: Option<Color>,
     /// Material on the surface
     pub material: &'a dyn Material,
 }
 
-pub trait Hittable {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    /// Box that contains the whole object, `None` when the object is unbounded
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+/// A closed object with an inside and an outside, which can be combined with
+/// constructive solid geometry
+pub trait Solid: Hittable {
+    /// All hits along the whole line of the ray, ordered by t and including negative t
+    ///
+    /// Hits that are front facing enter the solid, the others leave it
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>>;
 }
 
 fn calculate_face_normal(ray: &Ray, outward_normal: &Vec3) -> (bool, Vec3) {
@@ -23,9 +44,9 @@ fn calculate_face_normal(ray: &Ray, outward_normal: &Vec3) -> (bool, Vec3) {
     let front_face = dot(&ray.dir, outward_normal) < 0.;
     // If it is not front-facing invert the normal so it points against the ray
     let normal = if front_face {
-        outward_normal.clone()
+        *outward_normal
     } else {
-        -outward_normal.clone()
+        -*outward_normal
     };
 
     (front_face, normal)
@@ -39,19 +60,49 @@ impl<'a> HitRecord<'a> {
         t: f64,
         material: &'a dyn Material,
     ) -> Self {
-        let (front_face, normal) = calculate_face_normal(&ray, &normal);
+        let (front_face, normal) = calculate_face_normal(ray, normal);
         HitRecord {
             p: hit_point,
             normal,
             t,
+            u: 0.,
+            v: 0.,
             front_face,
+            vertex_color: None,
             material,
         }
     }
+
+    /// Use a shading normal that differs from the geometric normal, e.g. an
+    /// interpolated vertex normal
+    ///
+    /// Whether the hit is front facing is still decided by the geometric normal, the
+    /// shading normal is flipped to the same side as the stored normal
+    pub fn with_shading_normal(mut self, outward_normal: &Vec3) -> Self {
+        self.normal = if self.front_face {
+            *outward_normal
+        } else {
+            -*outward_normal
+        };
+        self
+    }
+
+    /// Set the texture coordinates of the hit point
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.u = u;
+        self.v = v;
+        self
+    }
+
+    /// Set the color of the surface at the hit point
+    pub fn with_vertex_color(mut self, color: Color) -> Self {
+        self.vertex_color = Some(color);
+        self
+    }
 }
 
 impl Hittable for Vec<&dyn Hittable> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
         let mut hit_record = None;
@@ -65,4 +116,42 @@ impl Hittable for Vec<&dyn Hittable> {
         });
         hit_record
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.iter()
+            .map(|hittable| hittable.bounding_box())
+            .try_fold(Aabb::empty(), |bbox, other| Some(bbox.surrounding(&other?)))
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Box<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+}
+
+impl<S: Solid + ?Sized> Solid for Box<S> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        (**self).intersections(r)
+    }
+}
+
+impl<S: Solid + ?Sized> Solid for Arc<S> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        (**self).intersections(r)
+    }
 }
@@ -0,0 +1,453 @@
+use crate::color::{self, Color, ToneMap};
+use crate::render::Sample;
+use crate::vec3::Vec3;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Settings used to turn the linear colors of an image into displayable colors
+#[derive(Debug, Clone)]
+pub struct ExportSettings {
+    /// Tone mapping that is applied to the linear colors
+    pub tone_map: ToneMap,
+    /// Gamma used to encode the tone mapped colors
+    pub gamma: f64,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            tone_map: ToneMap::Clamp,
+            gamma: 2.0,
+        }
+    }
+}
+
+impl ExportSettings {
+    /// Convert a linear color to a displayable color in the [0, 1] range
+    pub fn to_display(&self, c: &Color) -> Color {
+        color::gamma_correct(&self.tone_map.apply(c), self.gamma)
+    }
+}
+
+/// Bit depth of the channels in an encoded image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+/// File formats an image can be encoded in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// PNG with the given bit depth
+    Png(BitDepth),
+    /// Binary (P6) PPM
+    Ppm,
+    /// Portable float map, stores the linear colors without tone mapping
+    Pfm,
+    /// OpenEXR, stores the linear colors together with the albedo, normal and depth
+    Exr,
+}
+
+impl ImageFormat {
+    /// Select the format based on the extension of the path, PNG files use 8 bits
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png(BitDepth::Eight)),
+            "ppm" => Some(ImageFormat::Ppm),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+}
+
+/// Quantize a value in the [0, 1] range to an integer in [0, max]
+fn quantize(x: f64, max: u32) -> u32 {
+    ((max as f64 + 1.) * color::clamp(x, 0., 1.)).min(max as f64) as u32
+}
+
+/// A framebuffer accumulating linear HDR samples for every pixel
+///
+/// Next to the color, the albedo, normal and depth of the first surface hit are
+/// accumulated as separate layers. Pixels are stored row by row, starting at the top left
+/// of the image
+pub struct Image {
+    width: u32,
+    height: u32,
+    /// Sum of all samples for each pixel, the depth only of those that hit a surface
+    accumulated: Vec<Sample>,
+    /// Number of samples that were added for each pixel
+    samples: Vec<u32>,
+    /// Number of samples that hit a surface for each pixel
+    hits: Vec<u32>,
+}
+
+impl Image {
+    /// Create an image without any samples
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        let empty = Sample {
+            color: Color::zero(),
+            albedo: Color::zero(),
+            normal: Vec3::zero(),
+            depth: 0.,
+        };
+        Self {
+            width,
+            height,
+            accumulated: vec![empty; size],
+            samples: vec![0; size],
+            hits: vec![0; size],
+        }
+    }
+
+    /// Width of the image in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Add a single sample to the pixel at (x, y)
+    pub fn add_sample(&mut self, x: u32, y: u32, sample: &Sample) {
+        let index = self.index(x, y);
+        let mut sample = *sample;
+        // Samples that miss have an infinite depth, which would swamp the others
+        if sample.depth.is_finite() {
+            self.hits[index] += 1;
+        } else {
+            sample.depth = 0.;
+        }
+        self.accumulated[index] += sample;
+        self.samples[index] += 1;
+    }
+
+    /// Add all samples of another image, placing its top left corner at (x, y)
+    pub fn add_image(&mut self, other: &Image, x: u32, y: u32) {
+        for oy in 0..other.height {
+            for ox in 0..other.width {
+                let index = self.index(x + ox, y + oy);
+                let other_index = other.index(ox, oy);
+                self.accumulated[index] += other.accumulated[other_index];
+                self.samples[index] += other.samples[other_index];
+                self.hits[index] += other.hits[other_index];
+            }
+        }
+    }
+
+    /// Number of samples that were added to the pixel at (x, y)
+    pub fn sample_count(&self, x: u32, y: u32) -> u32 {
+        self.samples[self.index(x, y)]
+    }
+
+    /// The averaged sample of the pixel at (x, y), zero when it has no samples
+    ///
+    /// The depth is averaged over the samples that hit a surface, and is infinite when
+    /// none did. The normal is scaled back to unit length
+    pub fn sample(&self, x: u32, y: u32) -> Sample {
+        let index = self.index(x, y);
+        let accumulated = &self.accumulated[index];
+        let scale = match self.samples[index] {
+            0 => 0.,
+            count => 1.0 / count as f64,
+        };
+        let normal = if accumulated.normal.length_squared() > 0. {
+            accumulated.normal.unit_vector()
+        } else {
+            Vec3::zero()
+        };
+        Sample {
+            color: accumulated.color * scale,
+            albedo: accumulated.albedo * scale,
+            normal,
+            depth: match self.hits[index] {
+                0 => f64::INFINITY,
+                hits => accumulated.depth / hits as f64,
+            },
+        }
+    }
+
+    /// The linear color of the pixel at (x, y), black when it has no samples
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.sample(x, y).color
+    }
+
+    /// Write the image in the given format
+    pub fn write(
+        &self,
+        out: &mut impl Write,
+        format: ImageFormat,
+        export: &ExportSettings,
+    ) -> io::Result<()> {
+        match format {
+            ImageFormat::Png(bit_depth) => self.write_png(out, bit_depth, export),
+            ImageFormat::Ppm => self.write_ppm(out, export),
+            ImageFormat::Pfm => self.write_pfm(out),
+            ImageFormat::Exr => self.write_exr(out),
+        }
+    }
+
+    /// Save the image to a file in the given format
+    pub fn save(
+        &self,
+        path: &Path,
+        format: ImageFormat,
+        export: &ExportSettings,
+    ) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out, format, export)?;
+        out.flush()
+    }
+
+    /// Write the image as a PNG file
+    pub fn write_png(
+        &self,
+        out: &mut impl Write,
+        bit_depth: BitDepth,
+        export: &ExportSettings,
+    ) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_source_gamma(png::ScaledFloat::new((1.0 / export.gamma) as f32));
+
+        let mut data = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c = export.to_display(&self.pixel(x, y));
+                for channel in [c.x, c.y, c.z].iter() {
+                    match bit_depth {
+                        BitDepth::Eight => data.push(quantize(*channel, 255) as u8),
+                        BitDepth::Sixteen => data.extend_from_slice(
+                            &(quantize(*channel, u16::MAX as u32) as u16).to_be_bytes(),
+                        ),
+                    }
+                }
+            }
+        }
+        encoder.set_depth(match bit_depth {
+            BitDepth::Eight => png::BitDepth::Eight,
+            BitDepth::Sixteen => png::BitDepth::Sixteen,
+        });
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&data).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+
+    /// Write the image as a binary (P6) PPM file
+    pub fn write_ppm(&self, out: &mut impl Write, export: &ExportSettings) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let mut row = Vec::with_capacity(3 * self.width as usize);
+        for y in 0..self.height {
+            row.clear();
+            for x in 0..self.width {
+                let c = export.to_display(&self.pixel(x, y));
+                row.extend_from_slice(&[
+                    quantize(c.x, 255) as u8,
+                    quantize(c.y, 255) as u8,
+                    quantize(c.z, 255) as u8,
+                ]);
+            }
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+
+    /// Write the linear colors of the image as a little endian PFM file
+    pub fn write_pfm(&self, out: &mut impl Write) -> io::Result<()> {
+        // A negative scale marks the data as little endian
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        // Rows are stored from the bottom to the top
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let c = self.pixel(x, y);
+                for channel in [c.x, c.y, c.z].iter() {
+                    out.write_all(&(*channel as f32).to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the image as an OpenEXR file with all layers
+    ///
+    /// The linear color is stored in the R, G and B channels, the depth in Z, the albedo
+    /// in albedo.R, albedo.G and albedo.B and the normal in normal.X, normal.Y and
+    /// normal.Z. Pixels where nothing was hit have an infinite depth
+    pub fn write_exr(&self, out: &mut impl Write) -> io::Result<()> {
+        use exr::prelude::{
+            AnyChannel, AnyChannels, Encoding, FlatSamples, Layer, LayerAttributes, Vec2,
+            WritableImage,
+        };
+
+        let samples: Vec<Sample> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.sample(x, y))
+            .collect();
+        let channel = |name: &str, value: fn(&Sample) -> f64| {
+            let values = samples.iter().map(|s| value(s) as f32).collect();
+            AnyChannel::new(name, FlatSamples::F32(values))
+        };
+        let channels = vec![
+            channel("R", |s| s.color.x),
+            channel("G", |s| s.color.y),
+            channel("B", |s| s.color.z),
+            channel("Z", |s| s.depth),
+            channel("albedo.R", |s| s.albedo.x),
+            channel("albedo.G", |s| s.albedo.y),
+            channel("albedo.B", |s| s.albedo.z),
+            channel("normal.X", |s| s.normal.x),
+            channel("normal.Y", |s| s.normal.y),
+            channel("normal.Z", |s| s.normal.z),
+        ];
+
+        let layer = Layer::new(
+            Vec2(self.width as usize, self.height as usize),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels.into()),
+        );
+        // The encoder needs to seek, so encode into memory first
+        let mut buffer = io::Cursor::new(Vec::new());
+        exr::prelude::Image::from_layer(layer)
+            .write()
+            .to_buffered(&mut buffer)
+            .map_err(io::Error::other)?;
+        out.write_all(buffer.get_ref())
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exr::prelude::{read, FlatSamples, ReadChannels, ReadLayers};
+
+    /// An image with a single sample of each color, from left to right or top to bottom
+    fn image(width: u32, height: u32, colors: &[Color]) -> Image {
+        let mut image = Image::new(width, height);
+        for (i, color) in colors.iter().enumerate() {
+            let sample = Sample {
+                color: *color,
+                albedo: Color::zero(),
+                normal: Vec3::zero(),
+                depth: 0.,
+            };
+            image.add_sample(i as u32 % width, i as u32 / width, &sample);
+        }
+        image
+    }
+
+    fn encode(image: &Image, format: ImageFormat) -> Vec<u8> {
+        let mut out = Vec::new();
+        image
+            .write(&mut out, format, &ExportSettings::default())
+            .unwrap();
+        out
+    }
+
+    fn sample(normal: Vec3, depth: f64) -> Sample {
+        Sample {
+            color: Color::new(0.5, 0.5, 0.5),
+            albedo: Color::new(1., 0., 0.),
+            normal,
+            depth,
+        }
+    }
+
+    fn floats(data: &[u8]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn writes_png_bit_depth() {
+        let image = image(2, 1, &[Color::new(1., 0., 0.), Color::new(0., 0., 1.)]);
+        for (bit_depth, bits) in [(BitDepth::Eight, 8), (BitDepth::Sixteen, 16)] {
+            let data = encode(&image, ImageFormat::Png(bit_depth));
+            assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+            // The IHDR chunk comes first, with the width, height, bit depth and color type
+            assert_eq!(&data[12..16], b"IHDR");
+            assert_eq!(&data[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+            assert_eq!(data[24], bits);
+            assert_eq!(data[25], 2);
+        }
+    }
+
+    #[test]
+    fn writes_binary_ppm() {
+        let image = image(2, 1, &[Color::new(1., 0., 0.25), Color::new(0., 1., 1.)]);
+        let data = encode(&image, ImageFormat::Ppm);
+        let header = b"P6\n2 1\n255\n";
+        assert_eq!(&data[..header.len()], header);
+        // A quarter is encoded with a gamma of 2
+        assert_eq!(&data[header.len()..], &[255, 0, 128, 0, 255, 255]);
+    }
+
+    #[test]
+    fn writes_little_endian_pfm() {
+        let image = image(2, 1, &[Color::new(2., 0.5, 0.), Color::new(0., 0., 1.)]);
+        let data = encode(&image, ImageFormat::Pfm);
+        let header = b"PF\n2 1\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        // Colors are stored without tone mapping
+        assert_eq!(floats(&data[header.len()..]), [2., 0.5, 0., 0., 0., 1.]);
+    }
+
+    #[test]
+    fn writes_pfm_rows_bottom_up() {
+        let image = image(1, 2, &[Color::new(1., 1., 1.), Color::new(0., 0.5, 0.)]);
+        let data = encode(&image, ImageFormat::Pfm);
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(floats(&data[header.len()..]), [0., 0.5, 0., 1., 1., 1.]);
+    }
+
+    #[test]
+    fn writes_exr_layers() {
+        let mut image = Image::new(2, 1);
+        // The left pixel is on a silhouette, the right one is only sky
+        image.add_sample(0, 0, &sample(Vec3::new(1., 0., 0.), 2.));
+        image.add_sample(0, 0, &sample(Vec3::new(0., 1., 0.), 4.));
+        image.add_sample(0, 0, &sample(Vec3::zero(), f64::INFINITY));
+        image.add_sample(1, 0, &sample(Vec3::zero(), f64::INFINITY));
+        let data = encode(&image, ImageFormat::Exr);
+
+        let exr = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(io::Cursor::new(data))
+            .unwrap();
+        let channels = &exr.layer_data.channel_data.list;
+        let channel = |name: &str| -> Vec<f32> {
+            let channel = channels
+                .iter()
+                .find(|channel| channel.name.eq(name))
+                .unwrap();
+            match &channel.sample_data {
+                FlatSamples::F32(values) => values.clone(),
+                _ => panic!("channel {} is not stored as f32", name),
+            }
+        };
+        assert_eq!(channel("Z"), [3., f32::INFINITY]);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_eq!(channel("normal.X"), [half, 0.]);
+        assert_eq!(channel("normal.Y"), [half, 0.]);
+        assert_eq!(channel("normal.Z"), [0., 0.]);
+        assert_eq!(channel("albedo.R"), [1., 1.]);
+        assert_eq!(channel("G"), [0.5, 0.5]);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::aabb::Aabb;
+use crate::geometry::{polynomial_roots, quadratic_roots};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::matrix::Mat4;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use std::sync::Arc;
+
+/// A quadric surface, all points where
+/// `a x² + b y² + c z² + d xy + e xz + f yz + g x + h y + i z + j = 0`
+///
+/// This includes spheres, ellipsoids, cylinders, cones, paraboloids and hyperboloids.
+/// Points where the polynomial is negative are inside the surface
+pub struct Quadric {
+    /// The coefficients as a symmetric matrix Q, so the polynomial is pᵀ Q p for the
+    /// point p = (x, y, z, 1)
+    q: Mat4,
+    /// Only the part of the surface inside the bounds is visible, most quadrics are
+    /// infinite
+    bounds: Option<Aabb>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Quadric {
+    /// Create a quadric from the coefficients `[a, b, c, d, e, f, g, h, i, j]`
+    pub fn new(coefficients: [f64; 10], bounds: Option<Aabb>, material: Arc<dyn Material>) -> Self {
+        let [a, b, c, d, e, f, g, h, i, j] = coefficients;
+        let q = Mat4::new([
+            [a, d / 2., e / 2., g / 2.],
+            [d / 2., b, f / 2., h / 2.],
+            [e / 2., f / 2., c, i / 2.],
+            [g / 2., h / 2., i / 2., j],
+        ]);
+        Self {
+            q,
+            bounds,
+            material,
+        }
+    }
+
+    /// Evaluate uᵀ Q v, where u and v are points when the flags are set and directions
+    /// otherwise
+    fn product(&self, u: &Vec3, u_point: bool, v: &Vec3, v_point: bool) -> f64 {
+        let w = |point: bool| if point { 1. } else { 0. };
+        let (u, v) = ([u.x, u.y, u.z, w(u_point)], [v.x, v.y, v.z, w(v_point)]);
+        (0..4)
+            .map(|row| u[row] * (0..4).map(|k| self.q.m[row][k] * v[k]).sum::<f64>())
+            .sum()
+    }
+}
+
+impl Hittable for Quadric {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (lo, hi) = match &self.bounds {
+            Some(bounds) => bounds.intersect(r, t_min, t_max)?,
+            None => (t_min, t_max),
+        };
+        // Hits exactly on the bounds are kept, e.g. a cylinder touching them
+        let visible = |t: f64| t >= lo && t <= hi && t > t_min && t < t_max;
+
+        // (o + t d)ᵀ Q (o + t d) = 0
+        let (o, d) = (&r.origin, &r.dir);
+        let a = self.product(d, false, d, false);
+        let b = 2. * self.product(d, false, o, true);
+        let c = self.product(o, true, o, true);
+        let (t0, t1) = quadratic_roots(a, b, c)?;
+        let t = if visible(t0) {
+            t0
+        } else if visible(t1) {
+            t1
+        } else {
+            return None;
+        };
+
+        // The gradient 2 Q p points to the outside, where the polynomial grows
+        let p = r.at(t);
+        let row = |i: usize| {
+            self.q.m[i][0] * p.x + self.q.m[i][1] * p.y + self.q.m[i][2] * p.z + self.q.m[i][3]
+        };
+        let normal = Vec3::new(row(0), row(1), row(2)).unit_vector();
+        Some(HitRecord::new(r, p, &normal, t, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+/// A ball of a [`Metaballs`] object
+#[derive(Debug, Clone, Copy)]
+pub struct Metaball {
+    pub center: Point3,
+    /// Distance at which the ball stops having influence
+    pub radius: f64,
+    /// Value of the field at the center, negative values carve into other balls
+    pub strength: f64,
+}
+
+/// Blobs that smoothly merge when they get close, the surface where the summed field
+/// of all balls equals the threshold
+///
+/// Each ball adds `strength * (1 - r² / radius²)³` to the field at distance r, and
+/// nothing beyond its radius. Along a ray this is a polynomial of degree 6 for every
+/// ball, so the surface can be found exactly between the points where the ray enters
+/// and leaves the balls
+pub struct Metaballs {
+    pub balls: Vec<Metaball>,
+    /// Field value at the surface, higher values make the blobs thinner
+    pub threshold: f64,
+    pub material: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+/// Multiply two polynomials with their coefficients ordered from the constant term up
+fn multiply_polynomials(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut result = vec![0.; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            result[i + j] += x * y;
+        }
+    }
+    result
+}
+
+impl Metaballs {
+    pub fn new(balls: Vec<Metaball>, threshold: f64, material: Arc<dyn Material>) -> Self {
+        let bbox = balls.iter().fold(Aabb::empty(), |bbox, ball| {
+            let extent = Vec3::new(ball.radius, ball.radius, ball.radius);
+            bbox.surrounding(&Aabb::new(ball.center - extent, ball.center + extent))
+        });
+        Self {
+            balls,
+            threshold,
+            material,
+            bbox,
+        }
+    }
+
+    /// Squared distance to the center of a ball relative to its radius along the ray,
+    /// as the coefficients of a quadratic polynomial in t
+    fn relative_distance(ball: &Metaball, r: &Ray) -> [f64; 3] {
+        let oc = r.origin - ball.center;
+        let scale = 1. / (ball.radius * ball.radius);
+        [
+            oc.dot(&oc) * scale,
+            2. * oc.dot(&r.dir) * scale,
+            r.dir.dot(&r.dir) * scale,
+        ]
+    }
+
+    /// Outward normal, against the gradient of the field which grows to the inside
+    fn normal(&self, p: &Point3) -> Vec3 {
+        let gradient = self.balls.iter().fold(Vec3::zero(), |gradient, ball| {
+            let offset = p - ball.center;
+            let scale = 1. / (ball.radius * ball.radius);
+            let falloff = 1. - offset.length_squared() * scale;
+            if falloff <= 0. {
+                return gradient;
+            }
+            gradient + offset * (-6. * ball.strength * falloff * falloff * scale)
+        });
+        (-gradient).unit_vector()
+    }
+}
+
+impl Hittable for Metaballs {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t_min, t_max) = self.bbox.intersect(r, t_min, t_max)?;
+
+        // Each ball only contributes between where the ray enters and leaves it
+        let mut spans = Vec::new();
+        for ball in &self.balls {
+            let [c, b, a] = Self::relative_distance(ball, r);
+            if let Some((t0, t1)) = quadratic_roots(a, b, c - 1.) {
+                if t0 < t1 && t1 > t_min && t0 < t_max {
+                    spans.push((t0.max(t_min), t1.min(t_max), ball));
+                }
+            }
+        }
+        let mut bounds: Vec<f64> = spans.iter().flat_map(|&(t0, t1, _)| [t0, t1]).collect();
+        bounds.sort_by(f64::total_cmp);
+
+        for part in bounds.windows(2) {
+            let (lo, hi) = (part[0], part[1]);
+            if lo >= hi {
+                continue;
+            }
+            let middle = 0.5 * (lo + hi);
+            let mut field = vec![-self.threshold];
+            for &(t0, t1, ball) in &spans {
+                if t0 > middle || t1 < middle {
+                    continue;
+                }
+                // strength * (1 - q)³
+                let q = Self::relative_distance(ball, r);
+                let falloff = [1. - q[0], -q[1], -q[2]];
+                let cube =
+                    multiply_polynomials(&multiply_polynomials(&falloff, &falloff), &falloff);
+                field.resize(field.len().max(cube.len()), 0.);
+                for (sum, c) in field.iter_mut().zip(&cube) {
+                    *sum += ball.strength * c;
+                }
+            }
+            let t = polynomial_roots(&field, lo, hi)
+                .into_iter()
+                .find(|&t| t > t_min && t < t_max);
+            if let Some(t) = t {
+                let p = r.at(t);
+                let normal = self.normal(&p);
+                return Some(HitRecord::new(r, p, &normal, t, &*self.material));
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::gray;
+
+    #[test]
+    fn quadric_sphere_and_cylinder() {
+        // x² + y² + z² - 1 = 0
+        let sphere = Quadric::new([1., 1., 1., 0., 0., 0., 0., 0., 0., -1.], None, gray());
+        let ray = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+        let hit = sphere.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-12);
+        assert!((hit.normal - Vec3::new(0., 0., 1.)).length() < 1e-12);
+        assert!(hit.front_face);
+
+        // An infinite cylinder x² + z² = 1 around the y axis, cut off by the bounds
+        let bounds = Aabb::new(Point3::new(-1., 0., -1.), Point3::new(1., 2., 1.));
+        let cylinder = Quadric::new(
+            [1., 0., 1., 0., 0., 0., 0., 0., 0., -1.],
+            Some(bounds),
+            gray(),
+        );
+        let side = Ray::new(Point3::new(0., 1., 5.), Vec3::new(0., 0., -1.));
+        let hit = cylinder.hit(&side, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-12);
+        let above = Ray::new(Point3::new(0., 3., 5.), Vec3::new(0., 0., -1.));
+        assert!(cylinder.hit(&above, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn single_metaball_is_sphere() {
+        let ball = Metaball {
+            center: Point3::zero(),
+            radius: 2.,
+            strength: 1.,
+        };
+        // (1 - r² / 4)³ = 1/8 at r² = 2
+        let blob = Metaballs::new(vec![ball], 0.125, gray());
+        let ray = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+        let hit = blob.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.p.z - 2f64.sqrt()).abs() < 1e-9);
+        assert!((hit.normal - Vec3::new(0., 0., 1.)).length() < 1e-9);
+    }
+
+    #[test]
+    fn metaballs_merge() {
+        let ball = |x: f64| Metaball {
+            center: Point3::new(x, 0., 0.),
+            radius: 1.,
+            strength: 1.,
+        };
+        let threshold = 0.07;
+        let ray = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+
+        // A single ball at distance 0.8 is too weak at the origin, (1 - 0.64)³ ≈ 0.047
+        let single = Metaballs::new(vec![ball(-0.8)], threshold, gray());
+        assert!(single.hit(&ray, 0.001, f64::INFINITY).is_none());
+
+        // Together the two balls bridge the gap
+        let pair = Metaballs::new(vec![ball(-0.8), ball(0.8)], threshold, gray());
+        let hit = pair.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!(hit.p.z > 0.);
+        assert!(hit.normal.z > 0.99);
+    }
+}
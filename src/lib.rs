@@ -0,0 +1,39 @@
+//! A small ray tracer based on "Ray Tracing in One Weekend"
+//!
+//! The [`render`](render::render) function renders a world of
+//! [`Hittable`](hittable::Hittable) objects as seen from a
+//! [`Camera`](camera::Camera) into an [`Image`](image::Image).
+pub mod aabb;
+pub mod builtin;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod csg;
+pub mod curve;
+pub mod distribution;
+pub mod environment;
+pub mod geometry;
+pub mod gltf;
+pub mod heightfield;
+pub mod hittable;
+pub mod image;
+pub mod implicit;
+pub mod load;
+pub mod material;
+pub mod matrix;
+pub mod mesh;
+pub mod obj;
+pub mod ply;
+pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod scene;
+pub mod sdf;
+pub mod stl;
+pub mod subdivision;
+pub mod texture;
+pub mod transform;
+pub mod vec3;
+
+pub use crate::image::{BitDepth, ExportSettings, Image, ImageFormat};
+pub use crate::render::{color_ray, render, render_pixel, sample_ray, RenderSettings, Sample};
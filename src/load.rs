@@ -0,0 +1,51 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Error that occurs while loading a mesh, image or other file
+#[derive(Debug)]
+pub struct LoadError {
+    /// File that could not be loaded, `None` for data that was not read from a file
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl LoadError {
+    /// The error for data read from the file at path, unless it already has a file
+    pub(crate) fn in_file(mut self, path: &Path) -> Self {
+        self.path.get_or_insert_with(|| path.to_path_buf());
+        self
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "could not load {}: {}", path.display(), self.message),
+            None => write!(f, "could not load data: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Read a whole file into memory
+pub(crate) fn read_file(path: &Path) -> Result<Vec<u8>, LoadError> {
+    std::fs::read(path).map_err(|e| LoadError {
+        path: Some(path.to_path_buf()),
+        message: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_missing_file() {
+        let error = read_file(Path::new("scenes/missing.png")).unwrap_err();
+        assert_eq!(error.path.as_deref(), Some(Path::new("scenes/missing.png")));
+        assert!(error
+            .to_string()
+            .starts_with("could not load scenes/missing.png: "));
+    }
+}
@@ -1,110 +1,185 @@
-mod camera;
-mod color;
-mod geometry;
-mod hittable;
-mod material;
-mod ray;
-mod vec3;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Parser, Subcommand};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use trace_me::builtin::{builtin_scene, BUILTIN_SCENES};
+use trace_me::color::ToneMap;
+use trace_me::image::{BitDepth, ExportSettings, ImageFormat};
+use trace_me::render::render;
+use trace_me::scene::load_scene;
 
-use crate::camera::CameraBuilder;
-use crate::hittable::Hittable;
-use crate::vec3::unit_vector;
-use color::Color;
-use geometry::Sphere;
-use material::{Dialectric, Lambertian, Metal};
-use rand::prelude::*;
-use ray::Ray;
-use vec3::{Point3, Vec3};
+/// Exit code for invalid command line arguments, the same code clap uses
+const EXIT_USAGE: u8 = 2;
+/// Exit code for when the scene could not be loaded
+const EXIT_SCENE: u8 = 3;
+/// Exit code for when the output could not be written
+const EXIT_OUTPUT: u8 = 4;
 
-// Image
-const ASPECT_RATIO: f64 = 16.0 / 9.0;
-const WIDTH: u32 = 400;
-const HEIGHT: u32 = (WIDTH as f64 / ASPECT_RATIO) as u32;
-const MAX_DEPTH: u32 = 50;
-
-/// Color the ray according to y component
-pub fn color_ray(ray: &Ray, world: &impl Hittable, depth: u32) -> Vec3 {
-    if depth <= 0 {
-        return Color::zero();
-    }
-
-    if let Some(hit) = world.hit(ray, 0.0001, std::f64::INFINITY) {
-        // Color based on the material
-        if let Some(material) = hit.material.scatter(ray, &hit) {
-            return material.attenuation * color_ray(&material.scattered, world, depth - 1);
-        }
-    }
-    // Color based on y, scale from [-1, 1] to [0, 1]
-    let unit_dir = unit_vector(ray.dir);
-    let t = 0.5 * (unit_dir.y + 1.0);
-    // Linearly interpolate the ray color
-    (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+#[derive(Parser)]
+#[command(name = "trace_me", version, about = "A small ray tracer")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
 }
 
-/// Generate a random scene
-fn random_scene() {
-    let mut world: Vec<Box<dyn Hittable>> = Vec::new();
-    // Material on the ground
-    let material_ground = Box::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    // Set the ground plane
-    world.push(Box::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, material_ground)));
+#[derive(Subcommand)]
+enum Command {
+    /// Render a scene description to an image
+    Render(RenderArgs),
 }
 
-fn main() {
-    let material_ground = Lambertian::new(Color::new(0.8, 0.8, 0.0));
-    let material_center = Lambertian::new(Color::new(0.1, 0.2, 0.5));
-    let material_left = Dialectric::new(1.5);
-    let material_right = Metal::new(Color::new(0.7, 0.6, 0.2), 0.0);
-
+#[derive(clap::Args)]
+struct RenderArgs {
+    /// The scene to render, a TOML scene description or a glTF file
+    #[arg(required_unless_present = "builtin")]
+    scene: Option<PathBuf>,
+    /// Render a built-in scene instead of a scene description, e.g. "final"
+    #[arg(long, conflicts_with = "scene", value_parser = PossibleValuesParser::new(BUILTIN_SCENES))]
+    builtin: Option<String>,
+    /// Image width in pixels, keeps the aspect ratio of the scene unless --height is given
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+    /// Image height in pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    height: Option<u32>,
+    /// Number of samples per pixel
+    #[arg(long, alias = "samples-per-pixel", value_parser = clap::value_parser!(u32).range(1..))]
+    spp: Option<u32>,
+    /// Maximum number of bounces for a ray
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_depth: Option<u32>,
+    /// Number of rendering threads, uses all cores by default
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+    /// Seed for the random numbers, renders with the same seed give the same image
+    #[arg(long)]
+    seed: Option<u64>,
+    /// File to write the image to, the format is selected by the extension (.png, .ppm,
+    /// .pfm or .exr). Writes a PPM image to stdout when omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Bits per channel for PNG output
+    #[arg(long, default_value_t = 8, value_parser = PossibleValuesParser::new(["8", "16"]).map(|s| s.parse::<u8>().unwrap()))]
+    bit_depth: u8,
+    /// Tone mapping applied when exporting the image: clamp or reinhard
+    #[arg(long, default_value = "clamp")]
+    tonemap: ToneMap,
+    /// Gamma used to encode the exported image
+    #[arg(long, default_value_t = 2.0)]
+    gamma: f64,
+}
 
-    let big_sphere = Sphere {
-        center: Point3::new(0., -100.5, -1.0),
-        radius: 100.,
-        material: Box::new(material_ground),
+fn render_command(args: RenderArgs) -> ExitCode {
+    // Check the output before spending time on rendering
+    let output = match &args.output {
+        Some(path) => match ImageFormat::from_path(path) {
+            Some(ImageFormat::Png(_)) if args.bit_depth == 16 => {
+                Some((path, ImageFormat::Png(BitDepth::Sixteen)))
+            }
+            Some(format) => Some((path, format)),
+            None => {
+                eprintln!(
+                    "error: unsupported output format for {}, expected a .png, .ppm, .pfm or .exr file",
+                    path.display()
+                );
+                return ExitCode::from(EXIT_USAGE);
+            }
+        },
+        None => None,
     };
-    let sphere_center = Sphere {
-        center: Point3::new(0., 0., -1.0),
-        radius: 0.5,
-        material: Box::new(material_center),
+    if args.bit_depth == 16 && !matches!(output, Some((_, ImageFormat::Png(_)))) {
+        eprintln!("error: a bit depth of 16 is only supported for .png output");
+        return ExitCode::from(EXIT_USAGE);
+    }
+    if args.gamma.is_nan() || args.gamma <= 0.0 {
+        eprintln!("error: gamma must be larger than 0, got {}", args.gamma);
+        return ExitCode::from(EXIT_USAGE);
+    }
+    let export = ExportSettings {
+        tone_map: args.tonemap,
+        gamma: args.gamma,
     };
-    let sphere_left = Sphere {
-        center: Point3::new(-1.0, 0., -1.0),
-        radius: 0.5,
-        material: Box::new(material_left),
+
+    let scene = match (&args.builtin, &args.scene) {
+        (Some(name), _) => builtin_scene(name, args.seed.unwrap_or_default())
+            .ok_or_else(|| format!("unknown built-in scene `{}`", name)),
+        (None, Some(path)) => load_scene(path).map_err(|e| e.to_string()),
+        (None, None) => Err("no scene given".to_string()),
     };
-    let sphere_right = Sphere {
-        center: Point3::new(1.0, 0., -1.0),
-        radius: 0.5,
-        material: Box::new(material_right),
+    let mut scene = match scene {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(EXIT_SCENE);
+        }
     };
 
-    let mut hittables: Vec<&dyn Hittable> = Vec::new();
-    hittables.push(&sphere_center);
-    hittables.push(&big_sphere);
-    hittables.push(&sphere_left);
-    hittables.push(&sphere_right);
-
-    // Create camera
-    let look_from = Vec3::new(-2., 2., 1.);
-    let look_at = Vec3::new(0., 0., -1.);
+    // Override the settings of the scene with the command line
+    let settings = &mut scene.settings;
+    match (args.width, args.height) {
+        (Some(width), Some(height)) => {
+            settings.width = width;
+            settings.height = height;
+        }
+        (Some(width), None) => {
+            settings.height = ((width as f64 / settings.aspect_ratio()) as u32).max(1);
+            settings.width = width;
+        }
+        (None, Some(height)) => {
+            settings.width = ((height as f64 * settings.aspect_ratio()) as u32).max(1);
+            settings.height = height;
+        }
+        (None, None) => {}
+    }
+    if let Some(spp) = args.spp {
+        settings.samples_per_pixel = spp;
+    }
+    if let Some(max_depth) = args.max_depth {
+        settings.max_depth = max_depth;
+    }
+    if let Some(threads) = args.threads {
+        settings.threads = threads as usize;
+    }
+    if let Some(seed) = args.seed {
+        settings.seed = seed;
+    }
+    if let Err(e) = settings.validate() {
+        eprintln!("error: {}", e);
+        return ExitCode::from(EXIT_USAGE);
+    }
 
-    let camera = CameraBuilder::new(look_from, look_at).build();
-    let mut rng = rand::thread_rng();
-    let samples_per_pixel: u32 = 100;
+    eprintln!("Rendering {}x{} image", settings.width, settings.height);
+    let image = render(
+        &scene.world(),
+        &*scene.environment,
+        &scene.camera(),
+        &scene.settings,
+    );
 
-    println!("P3\n {} {}\n255", WIDTH, HEIGHT);
-    for h in (0..HEIGHT - 1).rev() {
-        eprintln!("\tScanlines remaining: {}", h);
-        for w in 0..WIDTH {
-            let mut pixel_color = Color::zero();
-            for _ in 0..samples_per_pixel {
-                let u = (w as f64 + rng.gen::<f64>()) / (WIDTH - 1) as f64;
-                let v = (h as f64 + rng.gen::<f64>()) / (HEIGHT - 1) as f64;
-                let ray = camera.get_ray(u, v);
-                pixel_color += color_ray(&ray, &hittables, MAX_DEPTH)
-            }
-            color::write_color(&pixel_color, samples_per_pixel);
+    let result = match output {
+        Some((path, format)) => image
+            .save(path, format, &export)
+            .map_err(|e| format!("could not write {}: {}", path.display(), e)),
+        None => {
+            let stdout = io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            image
+                .write_ppm(&mut out, &export)
+                .and_then(|_| out.flush())
+                .map_err(|e| format!("could not write image: {}", e))
         }
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        return ExitCode::from(EXIT_OUTPUT);
     }
     eprintln!("Done");
+    ExitCode::SUCCESS
+}
+
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Command::Render(args) => render_command(args),
+    }
 }
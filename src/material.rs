@@ -1,8 +1,12 @@
 use crate::color::Color;
 use crate::hittable::HitRecord;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::Vec3;
 use rand::Rng;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
 pub struct MaterialInfo {
     /// Definees how much the ray should be attenuated
@@ -11,29 +15,109 @@ pub struct MaterialInfo {
     pub scattered: Ray,
 }
 
-pub trait Material {
-    fn scatter(&self, ray_incoming: &Ray, hit_record: &HitRecord) -> Option<MaterialInfo>;
+pub trait Material: Send + Sync {
+    fn scatter(
+        &self,
+        ray_incoming: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<MaterialInfo>;
+
+    /// Light emitted by the surface at the hit point, black for surfaces that do not
+    /// emit light
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        Color::zero()
+    }
+
+    /// Density with respect to solid angle of scattering towards the direction
+    ///
+    /// Only diffuse materials, whose scattered light is the attenuation times this
+    /// density, return a density. Lights are then sampled directly for them, while
+    /// mirror-like materials can only find lights by scattering into them
+    fn scattering_pdf(&self, _hit_record: &HitRecord, _direction: &Vec3) -> Option<f64> {
+        None
+    }
+}
+
+/// Albedo of a surface, modulated by the vertex color of the hit if there is one
+fn surface_albedo(albedo: Color, hit_record: &HitRecord) -> Color {
+    match hit_record.vertex_color {
+        Some(color) => albedo * color,
+        None => albedo,
+    }
 }
 
 /// A diffuse surface
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    /// A diffuse surface with an albedo that varies over the surface
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_incoming: &Ray, hit_record: &HitRecord) -> Option<MaterialInfo> {
-        let scatter_direction = hit_record.normal + Vec3::random_unit_vector();
+    fn scatter(
+        &self,
+        _ray_incoming: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<MaterialInfo> {
+        let scatter_direction = hit_record.normal + Vec3::random_unit_vector(sampler);
         Some(MaterialInfo {
-            attenuation: self.albedo,
+            attenuation: surface_albedo(
+                self.albedo.value(hit_record.u, hit_record.v, &hit_record.p),
+                hit_record,
+            ),
             scattered: Ray::new(hit_record.p, scatter_direction),
         })
     }
+
+    /// The scattered directions follow the cosine to the normal
+    fn scattering_pdf(&self, hit_record: &HitRecord, direction: &Vec3) -> Option<f64> {
+        let cosine = hit_record.normal.dot(&direction.unit_vector());
+        Some(cosine.max(0.) / PI)
+    }
+}
+
+/// A surface that emits light to both sides and does not reflect any, e.g. an area
+/// light
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    /// Components above 1 make the light brighter than white
+    pub fn new(color: Color) -> Self {
+        Self::textured(Arc::new(SolidColor::new(color)))
+    }
+
+    /// A light with an emission that varies over the surface
+    pub fn textured(emit: Arc<dyn Texture>) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray_incoming: &Ray,
+        _hit_record: &HitRecord,
+        _sampler: &mut Sampler,
+    ) -> Option<MaterialInfo> {
+        None
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        self.emit.value(hit_record.u, hit_record.v, &hit_record.p)
+    }
 }
 
 /// A Metal surface
@@ -49,19 +133,24 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_incoming: &Ray, hit_record: &HitRecord) -> Option<MaterialInfo> {
+    fn scatter(
+        &self,
+        ray_incoming: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<MaterialInfo> {
         let reflected = ray_incoming.dir.unit_vector().reflect(&hit_record.normal);
 
         // Offset the reflection by a fuzziness factor
         let scattered = Ray::new(
             hit_record.p,
-            reflected + self.fuzz * Vec3::random_in_unit_sphere(),
+            reflected + self.fuzz * Vec3::random_in_unit_sphere(sampler),
         );
 
         // Only reflect from the outside
         if scattered.dir.dot(&hit_record.normal) > 0. {
             Some(MaterialInfo {
-                attenuation: self.albedo,
+                attenuation: surface_albedo(self.albedo, hit_record),
                 scattered,
             })
         } else {
@@ -88,7 +177,12 @@ fn schlick(cosine: f64, refraction_index: f64) -> f64 {
 }
 
 impl Material for Dialectric {
-    fn scatter(&self, ray_incoming: &Ray, hit_record: &HitRecord) -> Option<MaterialInfo> {
+    fn scatter(
+        &self,
+        ray_incoming: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<MaterialInfo> {
         let attenuation = Color::new(1.0, 1.0, 1.0);
         let etai_over_etat = if hit_record.front_face {
             1.0 / self.refraction_index
@@ -112,8 +206,7 @@ impl Material for Dialectric {
         }
         let reflect_prob = schlick(cos_theta, etai_over_etat);
 
-        let mut rnd = rand::thread_rng();
-        let random: f64 = rnd.gen();
+        let random: f64 = sampler.gen();
         if random < reflect_prob {
             let reflected = unit_direction.reflect(&hit_record.normal);
             return Some(MaterialInfo {
@@ -129,3 +222,104 @@ impl Material for Dialectric {
         })
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::vec3::Point3;
+
+    /// A gray diffuse material for objects in tests
+    pub(crate) fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    /// How a material responds to light, to check which material a file was loaded as
+    #[derive(Debug)]
+    pub(crate) enum Response {
+        Diffuse {
+            albedo: Color,
+        },
+        /// The spread is the furthest a reflection was seen from the mirror direction
+        Mirror {
+            albedo: Color,
+            spread: f64,
+        },
+        Refractive {
+            refraction_index: f64,
+        },
+        Light {
+            color: Color,
+        },
+    }
+
+    /// Find how a material responds to rays hitting the top of the xz plane
+    pub(crate) fn response(material: &dyn Material) -> Response {
+        let mut sampler = Sampler::from_seed(0);
+        let up = Vec3::new(0., 1., 0.);
+        // A ray coming in at 45 degrees
+        let ray = Ray::new(Point3::new(-1., 1., 0.), Vec3::new(1., -1., 0.));
+        let hit = HitRecord::new(&ray, Point3::zero(), &up, 1., material);
+        let scattered: Vec<MaterialInfo> = (0..1000)
+            .filter_map(|_| material.scatter(&ray, &hit, &mut sampler))
+            .collect();
+        let first = match scattered.first() {
+            Some(first) => first,
+            None => {
+                return Response::Light {
+                    color: material.emitted(&hit),
+                }
+            }
+        };
+        let directions = scattered
+            .iter()
+            .map(|info| info.scattered.dir.unit_vector());
+        // Diffuse reflections are spread around the normal instead of the mirror direction
+        let mean = directions.clone().fold(Vec3::zero(), |sum, d| sum + d) / scattered.len() as f64;
+        if mean.x.abs() < 0.1 {
+            return Response::Diffuse {
+                albedo: first.attenuation,
+            };
+        }
+        match directions.clone().find(|direction| direction.y < 0.) {
+            // Snell's law, with the sine of the incoming ray at 45 degrees
+            Some(refracted) => Response::Refractive {
+                refraction_index: 0.5f64.sqrt() / refracted.x,
+            },
+            None => {
+                let mirror = Vec3::new(1., 1., 0.).unit_vector();
+                Response::Mirror {
+                    albedo: first.attenuation,
+                    spread: directions.fold(0., |spread, direction| {
+                        f64::max(spread, (direction - mirror).length())
+                    }),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn responds_to_light() {
+        let gray = Color::new(0.5, 0.5, 0.5);
+        match response(&Lambertian::new(gray)) {
+            Response::Diffuse { albedo } => assert_eq!(albedo.x, 0.5),
+            other => panic!("expected a diffuse response, got {:?}", other),
+        }
+        match response(&Metal::new(gray, 0.1)) {
+            Response::Mirror { albedo, spread } => {
+                assert_eq!(albedo.y, 0.5);
+                assert!(spread > 0.05 && spread <= 0.1, "spread {}", spread);
+            }
+            other => panic!("expected a mirror response, got {:?}", other),
+        }
+        match response(&Dialectric::new(1.5)) {
+            Response::Refractive { refraction_index } => {
+                assert!((refraction_index - 1.5).abs() < 1e-12)
+            }
+            other => panic!("expected a refractive response, got {:?}", other),
+        }
+        match response(&DiffuseLight::new(Color::new(4., 4., 4.))) {
+            Response::Light { color } => assert_eq!(color.z, 4.),
+            other => panic!("expected a light, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::vec3::{Point3, Vec3};
+use std::ops;
+
+/// A 4x4 matrix for affine transformations of points and vectors
+///
+/// Points are treated as column vectors, so in `a * b` the transform `b` is applied
+/// first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    /// The elements, row by row
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    /// Create a matrix from its rows
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn translation(offset: &Vec3) -> Self {
+        let mut result = Self::identity();
+        result.m[0][3] = offset.x;
+        result.m[1][3] = offset.y;
+        result.m[2][3] = offset.z;
+        result
+    }
+
+    pub fn scaling(factors: &Vec3) -> Self {
+        let mut result = Self::identity();
+        result.m[0][0] = factors.x;
+        result.m[1][1] = factors.y;
+        result.m[2][2] = factors.z;
+        result
+    }
+
+    /// Rotation around the x axis, counter-clockwise when looking down the axis
+    pub fn rotation_x(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self::new([
+            [1., 0., 0., 0.],
+            [0., cos, -sin, 0.],
+            [0., sin, cos, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Rotation around the y axis, counter-clockwise when looking down the axis
+    pub fn rotation_y(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self::new([
+            [cos, 0., sin, 0.],
+            [0., 1., 0., 0.],
+            [-sin, 0., cos, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Rotation around the z axis, counter-clockwise when looking down the axis
+    pub fn rotation_z(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self::new([
+            [cos, -sin, 0., 0.],
+            [sin, cos, 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut result = *self;
+        for (row, values) in result.m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = self.m[column][row];
+            }
+        }
+        result
+    }
+
+    /// The inverse matrix, `None` when the matrix is singular
+    ///
+    /// Uses Gauss-Jordan elimination with partial pivoting
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inverse = Self::identity().m;
+        for column in 0..4 {
+            // Swap the row with the largest value into place for numerical stability
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column] == 0. || !a[pivot][column].is_finite() {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1. / a[column][column];
+            for k in 0..4 {
+                a[column][k] *= scale;
+                inverse[column][k] *= scale;
+            }
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = a[row][column];
+                for k in 0..4 {
+                    a[row][k] -= factor * a[column][k];
+                    inverse[row][k] -= factor * inverse[column][k];
+                }
+            }
+        }
+        Some(Self::new(inverse))
+    }
+
+    /// Determinant of the upper left 3x3 part, negative when the transform mirrors
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Transform a point, which includes the translation
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        self.transform_vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    /// Transform a direction, which ignores the translation
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    /// Transform a normal, given the inverse of the transform in `self`
+    ///
+    /// Normals transform with the inverse transpose to stay perpendicular to the
+    /// surface. The result is not normalized
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        self.transpose().transform_vector(n)
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl ops::Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut result = [[0.; 4]; 4];
+        for (row, values) in result.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[row][k] * rhs.m[k][column]).sum();
+            }
+        }
+        Mat4::new(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let transform = Mat4::translation(&Vec3::new(1., -2., 3.))
+            * Mat4::rotation_y(30.)
+            * Mat4::rotation_x(-70.)
+            * Mat4::scaling(&Vec3::new(2., 0.5, -3.));
+        let inverse = transform.inverse().unwrap();
+        let product = transform * inverse;
+        for row in 0..4 {
+            for column in 0..4 {
+                let expected = if row == column { 1. } else { 0. };
+                assert!((product.m[row][column] - expected).abs() < 1e-12);
+            }
+        }
+
+        let p = Point3::new(0.3, 4., -1.);
+        let back = inverse.transform_point(&transform.transform_point(&p));
+        assert!((back - p).length() < 1e-12);
+        assert!(transform.determinant3() < 0.);
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Mat4::scaling(&Vec3::new(1., 0., 1.)).inverse().is_none());
+    }
+
+    #[test]
+    fn rotations_are_counter_clockwise() {
+        let x = Vec3::new(1., 0., 0.);
+        let rotated = Mat4::rotation_z(90.).transform_vector(&x);
+        assert!((rotated - Vec3::new(0., 1., 0.)).length() < 1e-12);
+        let rotated = Mat4::rotation_y(90.).transform_vector(&x);
+        assert!((rotated - Vec3::new(0., 0., -1.)).length() < 1e-12);
+    }
+}
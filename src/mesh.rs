@@ -0,0 +1,240 @@
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::color::Color;
+use crate::geometry::{self, VertexAttributes};
+use crate::hittable::{HitRecord, Hittable};
+use crate::load::LoadError;
+use crate::material::{Lambertian, Material};
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use crate::{gltf, obj, ply, stl};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Albedo of the material used for meshes that do not specify one
+pub(crate) const DEFAULT_ALBEDO: f64 = 0.8;
+
+/// Vertex and face data of an indexed triangle mesh
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    /// Vertex normals used for smooth shading, empty when the mesh is flat shaded
+    pub normals: Vec<Vec3>,
+    /// Texture coordinates of the vertices, empty when the mesh has none
+    pub uvs: Vec<(f64, f64)>,
+    /// Colors of the vertices, empty when the mesh has none
+    pub colors: Vec<Color>,
+    /// Indices of the three vertices of every triangle, counter-clockwise from the front
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl MeshData {
+    fn vertices(&self, triangle: usize) -> [&Point3; 3] {
+        let [a, b, c] = self.triangles[triangle];
+        [
+            &self.positions[a as usize],
+            &self.positions[b as usize],
+            &self.positions[c as usize],
+        ]
+    }
+}
+
+/// The values of the three vertices of a triangle, `None` when the mesh has no values
+fn per_vertex<T>(values: &[T], [a, b, c]: [usize; 3]) -> Option<[&T; 3]> {
+    if values.is_empty() {
+        None
+    } else {
+        Some([&values[a], &values[b], &values[c]])
+    }
+}
+
+/// A triangle mesh with a single material
+///
+/// The vertex data is shared between all triangles. The BVH of the mesh only stores
+/// the index of every triangle
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    material: Arc<dyn Material>,
+    bvh: BvhNode<u32>,
+}
+
+impl TriangleMesh {
+    /// Create a mesh from all triangles in the data
+    ///
+    /// Fails when a triangle refers to a vertex that does not exist, or when the
+    /// normals, texture coordinates or colors are given for some but not all vertices
+    pub fn new(mesh: Arc<MeshData>, material: Arc<dyn Material>) -> Result<Self, LoadError> {
+        let vertex_count = mesh.positions.len();
+        let error = |message: &str| {
+            Err(LoadError {
+                path: None,
+                message: message.into(),
+            })
+        };
+        if !mesh.normals.is_empty() && mesh.normals.len() != vertex_count {
+            return error("mesh needs a normal for every vertex");
+        }
+        if !mesh.uvs.is_empty() && mesh.uvs.len() != vertex_count {
+            return error("mesh needs texture coordinates for every vertex");
+        }
+        if !mesh.colors.is_empty() && mesh.colors.len() != vertex_count {
+            return error("mesh needs a color for every vertex");
+        }
+        if mesh
+            .triangles
+            .iter()
+            .flatten()
+            .any(|&index| index as usize >= vertex_count)
+        {
+            return error("mesh triangle refers to a vertex that does not exist");
+        }
+
+        let bvh = BvhNode::with_bounds((0..mesh.triangles.len()).map(|index| {
+            let bbox = geometry::triangle_bounding_box(mesh.vertices(index));
+            (index as u32, bbox)
+        }));
+        Ok(Self {
+            mesh,
+            material,
+            bvh,
+        })
+    }
+
+    /// The vertex data of the mesh
+    pub fn data(&self) -> &Arc<MeshData> {
+        &self.mesh
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.material
+    }
+
+    fn hit_triangle(&self, index: usize, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mesh = &*self.mesh;
+        let [a, b, c] = mesh.triangles[index];
+        let indices = [a as usize, b as usize, c as usize];
+        let attributes = VertexAttributes {
+            normals: per_vertex(&mesh.normals, indices),
+            uvs: per_vertex(&mesh.uvs, indices).map(|[a, b, c]| [*a, *b, *c]),
+            colors: per_vertex(&mesh.colors, indices),
+        };
+        geometry::hit_triangle(
+            r,
+            t_min,
+            t_max,
+            mesh.vertices(index),
+            &attributes,
+            &*self.material,
+        )
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.bvh.hit_with(r, t_min, t_max, &|&index, t_min, t_max| {
+            self.hit_triangle(index as usize, r, t_min, t_max)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bvh.bounds())
+    }
+}
+
+/// Material used for a mesh that does not specify one
+///
+/// Meshes with vertex colors get a white albedo, so the vertex colors are used as is
+pub(crate) fn default_material(mesh: &MeshData) -> Arc<dyn Material> {
+    let albedo = if mesh.colors.is_empty() {
+        DEFAULT_ALBEDO
+    } else {
+        1.
+    };
+    Arc::new(Lambertian::new(Color::new(albedo, albedo, albedo)))
+}
+
+/// Load the meshes from a file, the format is selected by the extension
+///
+/// Wavefront OBJ (.obj), PLY (.ply), STL (.stl) and glTF (.gltf or .glb) files are
+/// supported, the cameras in glTF files are ignored. When a material is given it is
+/// used for all meshes, otherwise the materials from the file are used where the format
+/// has them
+pub fn load_mesh(
+    path: impl AsRef<Path>,
+    material: Option<Arc<dyn Material>>,
+) -> Result<Vec<TriangleMesh>, LoadError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    let mesh = match extension.as_deref() {
+        Some("obj") => return obj::load_obj(path, material),
+        Some("gltf") | Some("glb") => return Ok(gltf::load_gltf(path, material)?.meshes),
+        Some("ply") => ply::load_ply(path)?,
+        Some("stl") => stl::load_stl(path)?,
+        _ => {
+            let expected = "a .obj, .ply, .stl, .gltf or .glb file";
+            return Err(LoadError {
+                path: Some(path.to_path_buf()),
+                message: format!("unsupported mesh format, expected {}", expected),
+            });
+        }
+    };
+    let material = material.unwrap_or_else(|| default_material(&mesh));
+    let mesh = TriangleMesh::new(Arc::new(mesh), material).map_err(|e| e.in_file(path))?;
+    Ok(vec![mesh])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::gray;
+
+    fn quad() -> MeshData {
+        MeshData {
+            positions: vec![
+                Point3::new(0., 0., 0.),
+                Point3::new(1., 0., 0.),
+                Point3::new(1., 1., 0.),
+                Point3::new(0., 1., 0.),
+            ],
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+            ..MeshData::default()
+        }
+    }
+
+    #[test]
+    fn hits_shared_triangles() {
+        let mesh = TriangleMesh::new(Arc::new(quad()), gray()).unwrap();
+        let at = |x: f64, y: f64| Ray::new(Point3::new(x, y, 1.), Vec3::new(0., 0., -1.));
+        for (x, y) in [(0.75, 0.25), (0.25, 0.75)] {
+            let hit = mesh.hit(&at(x, y), 0.001, f64::INFINITY).unwrap();
+            assert_eq!(hit.t, 1.);
+            assert!(hit.front_face);
+        }
+        assert!(mesh.hit(&at(1.5, 0.5), 0.001, f64::INFINITY).is_none());
+        let bbox = mesh.bounding_box().unwrap();
+        assert!(bbox.min.x < 0. && bbox.max.y > 1.);
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let message = |data: MeshData| match TriangleMesh::new(Arc::new(data), gray()) {
+            Ok(_) => panic!("mesh data should be invalid"),
+            Err(e) => e.to_string(),
+        };
+        let mut missing_vertex = quad();
+        missing_vertex.triangles.push([2, 3, 4]);
+        assert_eq!(
+            message(missing_vertex),
+            "could not load data: mesh triangle refers to a vertex that does not exist"
+        );
+        let mut missing_normal = quad();
+        missing_normal.normals = vec![Vec3::new(0., 0., 1.); 3];
+        assert_eq!(
+            message(missing_normal),
+            "could not load data: mesh needs a normal for every vertex"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::color::Color;
+use crate::load::{self, LoadError};
+use crate::material::{Dialectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::{self, MeshData, TriangleMesh};
+use crate::vec3::{Point3, Vec3};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Convert an MTL material to the closest matching material
+///
+/// Materials with an emission color (`Ke`) become a [`DiffuseLight`]. Transparent
+/// materials (a dissolve below 1, or an illumination model with refraction) become a
+/// [`Dialectric`] with the optical density as refraction index.
+/// Illumination models with ray traced reflections become a [`Metal`] with the
+/// specular color as albedo, where a higher shininess gives a less fuzzy reflection.
+/// Everything else is a [`Lambertian`] with the diffuse color
+fn convert_material(material: &tobj::Material) -> Arc<dyn Material> {
+    let color = |c: [f32; 3]| Color::new(c[0] as f64, c[1] as f64, c[2] as f64);
+    let diffuse = material.diffuse.map(color).unwrap_or_else(|| {
+        Color::new(
+            mesh::DEFAULT_ALBEDO,
+            mesh::DEFAULT_ALBEDO,
+            mesh::DEFAULT_ALBEDO,
+        )
+    });
+    let illumination_model = material.illumination_model.unwrap_or(2);
+    // tobj does not know the emission color, so it is parsed here
+    let emission = material.unknown_param.get("Ke").and_then(|value| {
+        let components: Vec<f64> = value
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match components[..] {
+            [r, g, b] => Some(Color::new(r, g, b)),
+            _ => None,
+        }
+    });
+
+    let dissolved = material.dissolve.unwrap_or(1.) < 1.;
+    let refracting = matches!(illumination_model, 4 | 6 | 7 | 9);
+
+    if let Some(emission) = emission.filter(|emission| emission.length_squared() > 0.) {
+        Arc::new(DiffuseLight::new(emission))
+    } else if dissolved || refracting {
+        Arc::new(Dialectric::new(
+            material.optical_density.unwrap_or(1.5) as f64
+        ))
+    } else if matches!(illumination_model, 3 | 5 | 8) {
+        let albedo = material.specular.map(color).unwrap_or(diffuse);
+        // Phong exponents roughly map to a roughness of sqrt(2 / (Ns + 2))
+        let shininess = material.shininess.unwrap_or(0.).max(0.) as f64;
+        Arc::new(Metal::new(albedo, (2. / (shininess + 2.)).sqrt()))
+    } else {
+        Arc::new(Lambertian::new(diffuse))
+    }
+}
+
+/// Load all objects from a Wavefront OBJ file
+///
+/// Every object in the file becomes a separate mesh. Polygons are triangulated as a
+/// fan, vertex colors (`v x y z r g b`) are used to modulate the albedo. The materials
+/// are read from the MTL files the OBJ file refers to, unless a material is given,
+/// which is then used for all meshes
+pub fn load_obj(
+    path: impl AsRef<Path>,
+    material: Option<Arc<dyn Material>>,
+) -> Result<Vec<TriangleMesh>, LoadError> {
+    let path = path.as_ref();
+    let error = |message: String| LoadError {
+        path: Some(path.to_path_buf()),
+        message,
+    };
+    // Read the file up front so a missing file reports why, the MTL files are looked up
+    // next to it
+    let data = load::read_file(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let (models, materials) =
+        tobj::load_obj_buf(&mut &data[..], &tobj::GPU_LOAD_OPTIONS, |mtl_path| {
+            tobj::load_mtl(directory.join(mtl_path))
+        })
+        .map_err(|e| error(e.to_string()))?;
+
+    let materials: Vec<Arc<dyn Material>> = match &material {
+        Some(_) => Vec::new(),
+        None => materials
+            .map_err(|e| error(format!("could not load materials: {}", e)))?
+            .iter()
+            .map(convert_material)
+            .collect(),
+    };
+
+    let mut meshes = Vec::new();
+    for model in models {
+        let mesh = model.mesh;
+        if mesh.indices.is_empty() {
+            continue;
+        }
+        let mut data = MeshData {
+            positions: mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| Point3::new(p[0] as f64, p[1] as f64, p[2] as f64))
+                .collect(),
+            normals: mesh
+                .normals
+                .chunks_exact(3)
+                .map(|n| Vec3::new(n[0] as f64, n[1] as f64, n[2] as f64).unit_vector())
+                .collect(),
+            uvs: mesh
+                .texcoords
+                .chunks_exact(2)
+                .map(|uv| (uv[0] as f64, uv[1] as f64))
+                .collect(),
+            colors: mesh
+                .vertex_color
+                .chunks_exact(3)
+                .map(|c| Color::new(c[0] as f64, c[1] as f64, c[2] as f64))
+                .collect(),
+            triangles: mesh
+                .indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+        };
+        // Only use the normals and texture coordinates when every vertex has them
+        if data.normals.len() != data.positions.len() {
+            data.normals.clear();
+        }
+        if data.uvs.len() != data.positions.len() {
+            data.uvs.clear();
+        }
+        if data.colors.len() != data.positions.len() {
+            data.colors.clear();
+        }
+        let material = match (&material, mesh.material_id) {
+            (Some(material), _) => material.clone(),
+            (None, Some(id)) if id < materials.len() => materials[id].clone(),
+            (None, _) => mesh::default_material(&data),
+        };
+        meshes.push(TriangleMesh::new(Arc::new(data), material).map_err(|e| e.in_file(path))?);
+    }
+    Ok(meshes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::{response, Response};
+    use std::path::PathBuf;
+
+    fn shapes() -> Vec<TriangleMesh> {
+        let models = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("scenes/models");
+        load_obj(models.join("shapes.obj"), None).unwrap()
+    }
+
+    #[test]
+    fn triangulates_polygons() {
+        let meshes = shapes();
+        let triangles: Vec<usize> = meshes
+            .iter()
+            .map(|mesh| mesh.data().triangles.len())
+            .collect();
+        // The quads of the cube and the bottom of the pyramid are split in two
+        assert_eq!(triangles, [12, 6, 8]);
+
+        let cube = meshes[0].data();
+        let center = Point3::new(-1.5, 0.5, 0.);
+        let mut area = 0.;
+        for &[a, b, c] in &cube.triangles {
+            let [p0, p1, p2] = [a, b, c].map(|i| cube.positions[i as usize]);
+            let normal = (p1 - p0).cross(&(p2 - p0));
+            area += normal.length() / 2.;
+            // The fan keeps the counter-clockwise order of the faces
+            assert!(normal.dot(&(p0 - center)) > 0.);
+        }
+        assert!((area - 6.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn converts_mtl_materials() {
+        let meshes = shapes();
+        match response(&**meshes[0].material()) {
+            Response::Diffuse { albedo } => {
+                assert!((albedo - Color::new(0.7, 0.2, 0.2)).length() < 1e-6)
+            }
+            other => panic!("expected a diffuse cube, got {:?}", other),
+        }
+        match response(&**meshes[1].material()) {
+            // Ns 900 gives a fuzz of sqrt(2 / 902)
+            Response::Mirror { albedo, spread } => {
+                assert!((albedo - Color::new(0.8, 0.8, 0.9)).length() < 1e-6);
+                assert!(spread > 0. && spread <= 0.048, "spread {}", spread);
+            }
+            other => panic!("expected a mirror pyramid, got {:?}", other),
+        }
+        match response(&**meshes[2].material()) {
+            Response::Refractive { refraction_index } => {
+                assert!((refraction_index - 1.5).abs() < 1e-6)
+            }
+            other => panic!("expected a glass octahedron, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn converts_emission_and_dissolve() {
+        let mut lamp = tobj::Material {
+            diffuse: Some([0.5, 0.5, 0.5]),
+            illumination_model: Some(2),
+            ..tobj::Material::default()
+        };
+        lamp.unknown_param.insert("Ke".into(), "4 3 2".into());
+        match response(&*convert_material(&lamp)) {
+            Response::Light { color } => {
+                assert!((color - Color::new(4., 3., 2.)).length() < 1e-12)
+            }
+            other => panic!("expected a light, got {:?}", other),
+        }
+
+        // A black emission color does not make a light
+        lamp.unknown_param.insert("Ke".into(), "0 0 0".into());
+        assert!(matches!(
+            response(&*convert_material(&lamp)),
+            Response::Diffuse { .. }
+        ));
+
+        let faded = tobj::Material {
+            dissolve: Some(0.5),
+            optical_density: Some(1.3),
+            illumination_model: Some(2),
+            ..tobj::Material::default()
+        };
+        match response(&*convert_material(&faded)) {
+            Response::Refractive { refraction_index } => {
+                assert!((refraction_index - 1.3).abs() < 1e-6)
+            }
+            other => panic!("expected a refractive material, got {:?}", other),
+        }
+
+        let polished = tobj::Material {
+            specular: Some([0.9, 0.9, 0.9]),
+            illumination_model: Some(5),
+            ..tobj::Material::default()
+        };
+        assert!(matches!(
+            response(&*convert_material(&polished)),
+            Response::Mirror { .. }
+        ));
+    }
+}
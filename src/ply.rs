@@ -0,0 +1,497 @@
+use crate::color::Color;
+use crate::load::{self, LoadError};
+use crate::mesh::MeshData;
+use crate::vec3::{Point3, Vec3};
+use std::convert::TryInto;
+use std::path::Path;
+
+/// Encoding of the data following the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Types a property value can be stored as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Scalar::Int8),
+            "uchar" | "uint8" => Some(Scalar::UInt8),
+            "short" | "int16" => Some(Scalar::Int16),
+            "ushort" | "uint16" => Some(Scalar::UInt16),
+            "int" | "int32" => Some(Scalar::Int32),
+            "uint" | "uint32" => Some(Scalar::UInt32),
+            "float" | "float32" => Some(Scalar::Float32),
+            "double" | "float64" => Some(Scalar::Float64),
+            _ => None,
+        }
+    }
+
+    /// Size in bytes of a binary value
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::UInt8 => 1,
+            Scalar::Int16 | Scalar::UInt16 => 2,
+            Scalar::Int32 | Scalar::UInt32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+
+    /// Value that represents full intensity when the type is used for a color
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::UInt8 => u8::MAX as f64,
+            Scalar::UInt16 => u16::MAX as f64,
+            _ => 1.,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PropertyKind {
+    Scalar(Scalar),
+    /// A list of values, preceded by the number of values
+    List {
+        count: Scalar,
+        item: Scalar,
+    },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| names.contains(&property.name.as_str()))
+    }
+}
+
+/// Parse the header, returning its format, elements and the size of the header
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), String> {
+    const END: &[u8] = b"end_header";
+    let end = data
+        .windows(END.len())
+        .position(|window| window == END)
+        .ok_or("missing end_header")?;
+    let body = data[end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|newline| end + newline + 1)
+        .unwrap_or(data.len());
+    let header = std::str::from_utf8(&data[..end]).map_err(|_| "header is not valid text")?;
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err("not a PLY file".into());
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("unknown format `{}`", name)),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("invalid element count `{}`", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", kind @ .., name] => {
+                let scalar = |name: &str| {
+                    Scalar::parse(name).ok_or_else(|| format!("unknown property type `{}`", name))
+                };
+                let kind = match kind {
+                    ["list", count, item] => PropertyKind::List {
+                        count: scalar(count)?,
+                        item: scalar(item)?,
+                    },
+                    [kind] => PropertyKind::Scalar(scalar(kind)?),
+                    _ => return Err(format!("invalid property `{}`", line)),
+                };
+                elements
+                    .last_mut()
+                    .ok_or("property before the first element")?
+                    .properties
+                    .push(Property {
+                        name: name.to_string(),
+                        kind,
+                    });
+            }
+            _ => return Err(format!("invalid header line `{}`", line)),
+        }
+    }
+    let format = format.ok_or("missing format")?;
+    Ok((format, elements, body))
+}
+
+/// Source of the property values in the body of the file
+trait Values {
+    fn next(&mut self, scalar: Scalar) -> Result<f64, String>;
+}
+
+struct AsciiValues<'a> {
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl Values for AsciiValues<'_> {
+    fn next(&mut self, _scalar: Scalar) -> Result<f64, String> {
+        let token = self.tokens.next().ok_or("unexpected end of file")?;
+        token
+            .parse()
+            .map_err(|_| format!("invalid value `{}`", token))
+    }
+}
+
+struct BinaryValues<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Values for BinaryValues<'_> {
+    fn next(&mut self, scalar: Scalar) -> Result<f64, String> {
+        let size = scalar.size();
+        if self.data.len() < size {
+            return Err("unexpected end of file".into());
+        }
+        let (bytes, rest) = self.data.split_at(size);
+        self.data = rest;
+
+        // Convert the value to little endian
+        let mut buffer = [0u8; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.big_endian {
+            buffer[..size].reverse();
+        }
+        let b = &buffer;
+        Ok(match scalar {
+            Scalar::Int8 => b[0] as i8 as f64,
+            Scalar::UInt8 => b[0] as f64,
+            Scalar::Int16 => i16::from_le_bytes(b[..2].try_into().unwrap()) as f64,
+            Scalar::UInt16 => u16::from_le_bytes(b[..2].try_into().unwrap()) as f64,
+            Scalar::Int32 => i32::from_le_bytes(b[..4].try_into().unwrap()) as f64,
+            Scalar::UInt32 => u32::from_le_bytes(b[..4].try_into().unwrap()) as f64,
+            Scalar::Float32 => f32::from_le_bytes(b[..4].try_into().unwrap()) as f64,
+            Scalar::Float64 => f64::from_le_bytes(*b),
+        })
+    }
+}
+
+/// Read all values of one element, lists are returned separately
+fn read_element(
+    values: &mut dyn Values,
+    element: &Element,
+    scalars: &mut Vec<f64>,
+    lists: &mut Vec<Vec<f64>>,
+) -> Result<(), String> {
+    scalars.clear();
+    lists.clear();
+    for property in &element.properties {
+        match property.kind {
+            PropertyKind::Scalar(scalar) => scalars.push(values.next(scalar)?),
+            PropertyKind::List { count, item } => {
+                // Keep the scalar indices matching the property indices
+                scalars.push(0.);
+                let count = values.next(count)?;
+                if count < 0. {
+                    return Err(format!("negative list length {}", count));
+                }
+                let list = (0..count as usize)
+                    .map(|_| values.next(item))
+                    .collect::<Result<_, _>>()?;
+                lists.push(list);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_vertices(
+    values: &mut dyn Values,
+    element: &Element,
+    mesh: &mut MeshData,
+) -> Result<(), String> {
+    let property = |names: &[&str]| element.property(names);
+    let (x, y, z) = match (property(&["x"]), property(&["y"]), property(&["z"])) {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => return Err("vertices need an x, y and z property".into()),
+    };
+    let normal = match (property(&["nx"]), property(&["ny"]), property(&["nz"])) {
+        (Some(nx), Some(ny), Some(nz)) => Some((nx, ny, nz)),
+        _ => None,
+    };
+    let uv = match (
+        property(&["u", "s", "texture_u", "texture_s"]),
+        property(&["v", "t", "texture_v", "texture_t"]),
+    ) {
+        (Some(u), Some(v)) => Some((u, v)),
+        _ => None,
+    };
+    let color = match (
+        property(&["red", "diffuse_red"]),
+        property(&["green", "diffuse_green"]),
+        property(&["blue", "diffuse_blue"]),
+    ) {
+        (Some(r), Some(g), Some(b)) => Some((r, g, b)),
+        _ => None,
+    };
+    let color_scale = |index: usize| match element.properties[index].kind {
+        PropertyKind::Scalar(scalar) => scalar.color_scale(),
+        PropertyKind::List { .. } => 1.,
+    };
+
+    let (mut scalars, mut lists) = (Vec::new(), Vec::new());
+    for _ in 0..element.count {
+        read_element(values, element, &mut scalars, &mut lists)?;
+        let s = &scalars;
+        mesh.positions.push(Point3::new(s[x], s[y], s[z]));
+        if let Some((nx, ny, nz)) = normal {
+            mesh.normals
+                .push(Vec3::new(s[nx], s[ny], s[nz]).unit_vector());
+        }
+        if let Some((u, v)) = uv {
+            mesh.uvs.push((s[u], s[v]));
+        }
+        if let Some((r, g, b)) = color {
+            mesh.colors.push(Color::new(
+                s[r] / color_scale(r),
+                s[g] / color_scale(g),
+                s[b] / color_scale(b),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(
+    values: &mut dyn Values,
+    element: &Element,
+    mesh: &mut MeshData,
+) -> Result<(), String> {
+    let indices = element
+        .property(&["vertex_indices", "vertex_index"])
+        .ok_or("faces need a vertex_indices property")?;
+    // The position of the property among the lists of the element
+    let list = element.properties[..indices]
+        .iter()
+        .filter(|property| matches!(property.kind, PropertyKind::List { .. }))
+        .count();
+    if !matches!(element.properties[indices].kind, PropertyKind::List { .. }) {
+        return Err("vertex_indices must be a list".into());
+    }
+
+    let (mut scalars, mut lists) = (Vec::new(), Vec::new());
+    for _ in 0..element.count {
+        read_element(values, element, &mut scalars, &mut lists)?;
+        let face = &lists[list];
+        if face
+            .iter()
+            .any(|&index| index < 0. || index > u32::MAX as f64)
+        {
+            return Err("face refers to a vertex that does not exist".into());
+        }
+        // Triangulate polygons as a fan
+        for i in 1..face.len().saturating_sub(1) {
+            mesh.triangles
+                .push([face[0] as u32, face[i] as u32, face[i + 1] as u32]);
+        }
+    }
+    Ok(())
+}
+
+/// Parse a PLY file
+fn parse_ply(data: &[u8]) -> Result<MeshData, String> {
+    let (format, elements, body) = parse_header(data)?;
+    let body = &data[body..];
+    let mut values: Box<dyn Values> = match format {
+        Format::Ascii => Box::new(AsciiValues {
+            tokens: std::str::from_utf8(body)
+                .map_err(|_| "data is not valid text")?
+                .split_ascii_whitespace(),
+        }),
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => Box::new(BinaryValues {
+            data: body,
+            big_endian: format == Format::BinaryBigEndian,
+        }),
+    };
+
+    let mut mesh = MeshData::default();
+    let (mut scalars, mut lists) = (Vec::new(), Vec::new());
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(values.as_mut(), element, &mut mesh)?,
+            "face" => read_faces(values.as_mut(), element, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    read_element(values.as_mut(), element, &mut scalars, &mut lists)?;
+                }
+            }
+        }
+    }
+
+    let vertex_count = mesh.positions.len();
+    if mesh
+        .triangles
+        .iter()
+        .flatten()
+        .any(|&index| index as usize >= vertex_count)
+    {
+        return Err("face refers to a vertex that does not exist".into());
+    }
+    Ok(mesh)
+}
+
+/// Load a mesh from an ASCII or binary PLY file
+///
+/// The vertex positions, normals (nx, ny, nz), texture coordinates (u, v or s, t) and
+/// colors (red, green, blue) are read, integer colors are scaled to the [0, 1] range.
+/// Polygons are triangulated as a fan
+pub fn load_ply(path: impl AsRef<Path>) -> Result<MeshData, LoadError> {
+    let path = path.as_ref();
+    parse_ply(&load::read_file(path)?).map_err(|message| LoadError {
+        path: Some(path.to_path_buf()),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ascii() {
+        let source = "ply
+format ascii 1.0
+comment a colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+        let mesh = parse_ply(source.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+        assert!(mesh.normals.is_empty() && mesh.uvs.is_empty());
+        assert_eq!(mesh.colors[1].y, 1.);
+        assert_eq!(mesh.colors[3].x, 1.);
+        assert_eq!(mesh.positions[2].y, 1.);
+    }
+
+    fn binary_triangle(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let mut data = format!(
+            "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+             property float z\nproperty double nz\nelement edge 1\nproperty int vertex1\n\
+             property int vertex2\nelement face 1\nproperty uchar flags\n\
+             property list uchar uint vertex_indices\nend_header\n",
+            format
+        )
+        .into_bytes();
+        let f32_bytes = |x: f32| {
+            if big_endian {
+                x.to_be_bytes()
+            } else {
+                x.to_le_bytes()
+            }
+        };
+        for (x, y) in [(0., 0.), (1., 0.), (0., 1.)].iter() {
+            data.extend_from_slice(&f32_bytes(*x));
+            data.extend_from_slice(&f32_bytes(*y));
+            data.extend_from_slice(&f32_bytes(2.));
+            let nz = 1f64;
+            data.extend_from_slice(&if big_endian {
+                nz.to_be_bytes()
+            } else {
+                nz.to_le_bytes()
+            });
+        }
+        // The edge element is skipped
+        data.extend_from_slice(&[0; 8]);
+        data.push(7);
+        data.push(3);
+        for index in 0..3u32 {
+            data.extend_from_slice(&if big_endian {
+                index.to_be_bytes()
+            } else {
+                index.to_le_bytes()
+            });
+        }
+        data
+    }
+
+    #[test]
+    fn parses_binary() {
+        for &big_endian in [false, true].iter() {
+            let mesh = parse_ply(&binary_triangle(big_endian)).unwrap();
+            assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+            assert_eq!(mesh.positions[1].x, 1.);
+            assert_eq!(mesh.positions[2].y, 1.);
+            assert_eq!(mesh.positions[2].z, 2.);
+            // Only nz is given, which is not a complete normal
+            assert!(mesh.normals.is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let mut truncated = binary_triangle(false);
+        truncated.pop();
+        assert!(parse_ply(&truncated).is_err());
+        assert!(parse_ply(b"solid cube\nendsolid").is_err());
+
+        let out_of_bounds = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+                             property float y\nproperty float z\nelement face 1\n\
+                             property list uchar int vertex_indices\nend_header\n\
+                             0 0 0\n3 0 1 2\n";
+        assert!(parse_ply(out_of_bounds.as_bytes()).is_err());
+    }
+}
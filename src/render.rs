@@ -0,0 +1,496 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::environment::Environment;
+use crate::hittable::{HitRecord, Hittable};
+use crate::image::Image;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+use rand::Rng;
+use std::ops;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Settings that control how an image is rendered
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    /// Width of the image in pixels
+    pub width: u32,
+    /// Height of the image in pixels
+    pub height: u32,
+    /// Number of rays that are cast for every pixel
+    pub samples_per_pixel: u32,
+    /// Maximum number of times a ray may bounce
+    pub max_depth: u32,
+    /// Number of threads used for rendering, 0 uses all available cores
+    pub threads: usize,
+    /// Width and height of the tiles the image is split into
+    pub tile_size: u32,
+    /// Seed for the random numbers, the same seed always gives the same image
+    pub seed: u64,
+}
+
+impl RenderSettings {
+    /// The aspect ratio of the resulting image
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// Check that the settings can be used to render an image
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!(
+                "image size must be at least 1x1, got {}x{}",
+                self.width, self.height
+            ));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err("samples per pixel must be at least 1".to_string());
+        }
+        if self.max_depth == 0 {
+            return Err("max depth must be at least 1".to_string());
+        }
+        if self.tile_size == 0 {
+            return Err("tile size must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// The number of threads that will be used for rendering
+    pub fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            threads => threads,
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 400,
+            height: 225,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            threads: 0,
+            tile_size: 16,
+            seed: 0,
+        }
+    }
+}
+
+/// The result of tracing a single camera ray
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// Light arriving along the ray
+    pub color: Color,
+    /// Albedo of the first surface that was hit
+    pub albedo: Color,
+    /// Normal of the first surface that was hit, facing the ray
+    pub normal: Vec3,
+    /// Distance to the first surface that was hit, infinite when nothing was hit
+    pub depth: f64,
+}
+
+impl ops::AddAssign<Sample> for Sample {
+    fn add_assign(&mut self, rhs: Sample) {
+        self.color += rhs.color;
+        self.albedo += rhs.albedo;
+        self.normal += rhs.normal;
+        self.depth += rhs.depth;
+    }
+}
+
+/// Weight of a sample taken with the density `pdf` when another strategy could have
+/// taken it with `other_pdf`, by the power heuristic of multiple importance sampling
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    if other_pdf <= 0. {
+        return 1.;
+    }
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    a / (a + b)
+}
+
+/// Light leaving the hit point towards the origin of the ray, returned together with
+/// the attenuation of the surface
+///
+/// Diffuse surfaces also sample the environment directly, weighted against finding it
+/// through the scattered ray
+fn shade(
+    ray: &Ray,
+    hit: &HitRecord,
+    world: &impl Hittable,
+    environment: &dyn Environment,
+    depth: u32,
+    sampler: &mut Sampler,
+) -> (Color, Color) {
+    let emitted = hit.material.emitted(hit);
+    let material = match hit.material.scatter(ray, hit, sampler) {
+        Some(material) => material,
+        None => return (emitted, Color::zero()),
+    };
+
+    let mut color = emitted;
+    let scattering_pdf = hit.material.scattering_pdf(hit, &material.scattered.dir);
+    if scattering_pdf.is_some() {
+        if let Some((direction, light, light_pdf)) = environment.sample(sampler) {
+            let pdf = hit.material.scattering_pdf(hit, &direction).unwrap_or(0.);
+            let shadow = Ray::new(hit.p, direction);
+            if pdf > 0. && world.hit(&shadow, 0.0001, f64::INFINITY).is_none() {
+                let weight = power_heuristic(light_pdf, pdf);
+                color += material.attenuation * light * (pdf / light_pdf * weight);
+            }
+        }
+    }
+    let incoming = trace(
+        &material.scattered,
+        world,
+        environment,
+        depth - 1,
+        sampler,
+        scattering_pdf,
+    );
+    (
+        color + material.attenuation * incoming,
+        material.attenuation,
+    )
+}
+
+/// Light arriving along the ray, where `scattering_pdf` is the density of the diffuse
+/// scattering that produced the ray, if the environment was also sampled directly
+fn trace(
+    ray: &Ray,
+    world: &impl Hittable,
+    environment: &dyn Environment,
+    depth: u32,
+    sampler: &mut Sampler,
+    scattering_pdf: Option<f64>,
+) -> Color {
+    if depth == 0 {
+        return Color::zero();
+    }
+
+    match world.hit(ray, 0.0001, f64::INFINITY) {
+        Some(hit) => shade(ray, &hit, world, environment, depth, sampler).0,
+        None => {
+            let light = environment.color(&ray.dir);
+            match scattering_pdf {
+                Some(pdf) => light * power_heuristic(pdf, environment.pdf(&ray.dir)),
+                None => light,
+            }
+        }
+    }
+}
+
+/// Light arriving along the ray, from the surfaces it hits and the environment once it
+/// leaves the world
+pub fn color_ray(
+    ray: &Ray,
+    world: &impl Hittable,
+    environment: &dyn Environment,
+    depth: u32,
+    sampler: &mut Sampler,
+) -> Color {
+    trace(ray, world, environment, depth, sampler, None)
+}
+
+/// Trace a camera ray, also recording the first surface that was hit
+pub fn sample_ray(
+    ray: &Ray,
+    world: &impl Hittable,
+    environment: &dyn Environment,
+    depth: u32,
+    sampler: &mut Sampler,
+) -> Sample {
+    let miss = |color| Sample {
+        color,
+        albedo: Color::zero(),
+        normal: Vec3::zero(),
+        depth: f64::INFINITY,
+    };
+    if depth == 0 {
+        return miss(Color::zero());
+    }
+    let hit = match world.hit(ray, 0.0001, f64::INFINITY) {
+        Some(hit) => hit,
+        None => return miss(environment.color(&ray.dir)),
+    };
+
+    let (color, albedo) = shade(ray, &hit, world, environment, depth, sampler);
+    Sample {
+        color,
+        albedo,
+        normal: hit.normal,
+        depth: hit.t * ray.dir.length(),
+    }
+}
+
+/// A rectangular part of the image
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Split the image into tiles, row by row
+fn tiles(settings: &RenderSettings) -> Vec<Tile> {
+    let size = settings.tile_size;
+    let mut tiles = Vec::new();
+    for y in (0..settings.height).step_by(size as usize) {
+        for x in (0..settings.width).step_by(size as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: size.min(settings.width - x),
+                height: size.min(settings.height - y),
+            });
+        }
+    }
+    tiles
+}
+
+/// Trace sample `index` of the pixel at (x, y)
+fn trace_sample(
+    world: &impl Hittable,
+    environment: &dyn Environment,
+    camera: &Camera,
+    settings: &RenderSettings,
+    (x, y): (u32, u32),
+    index: u32,
+) -> Sample {
+    let mut sampler = Sampler::for_sample(settings.seed, x, y, index);
+    // The camera expects v to go up, while the image rows go down
+    let h = settings.height - 1 - y;
+    let u = (x as f64 + sampler.gen::<f64>()) / (settings.width - 1).max(1) as f64;
+    let v = (h as f64 + sampler.gen::<f64>()) / (settings.height - 1).max(1) as f64;
+    let ray = camera.get_ray(u, v, &mut sampler);
+    sample_ray(&ray, world, environment, settings.max_depth, &mut sampler)
+}
+
+/// Render all samples of the pixel at (x, y) and return their average
+///
+/// The result is identical to that pixel in the image returned by [`render`] with the
+/// same settings
+pub fn render_pixel(
+    world: &impl Hittable,
+    environment: &dyn Environment,
+    camera: &Camera,
+    settings: &RenderSettings,
+    x: u32,
+    y: u32,
+) -> Sample {
+    let mut image = Image::new(1, 1);
+    for index in 0..settings.samples_per_pixel {
+        let sample = trace_sample(world, environment, camera, settings, (x, y), index);
+        image.add_sample(0, 0, &sample);
+    }
+    image.sample(0, 0)
+}
+
+/// Render a single tile into an image the size of the tile
+fn render_tile(
+    world: &impl Hittable,
+    environment: &dyn Environment,
+    camera: &Camera,
+    settings: &RenderSettings,
+    tile: &Tile,
+) -> Image {
+    let mut image = Image::new(tile.width, tile.height);
+    for ty in 0..tile.height {
+        for tx in 0..tile.width {
+            let pixel = (tile.x + tx, tile.y + ty);
+            for index in 0..settings.samples_per_pixel {
+                let sample = trace_sample(world, environment, camera, settings, pixel, index);
+                image.add_sample(tx, ty, &sample);
+            }
+        }
+    }
+    image
+}
+
+/// Render the world in its environment as seen from the camera into an image
+///
+/// The image is split into tiles which are divided over the rendering threads
+pub fn render(
+    world: &impl Hittable,
+    environment: &dyn Environment,
+    camera: &Camera,
+    settings: &RenderSettings,
+) -> Image {
+    let tiles = tiles(settings);
+    let next_tile = AtomicUsize::new(0);
+    let image = Mutex::new(Image::new(settings.width, settings.height));
+
+    thread::scope(|scope| {
+        for _ in 0..settings.thread_count().min(tiles.len()) {
+            scope.spawn(|| {
+                // Keep taking tiles until all of them are rendered
+                while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                    let rendered = render_tile(world, environment, camera, settings, tile);
+                    image.lock().unwrap().add_image(&rendered, tile.x, tile.y);
+                }
+            });
+        }
+    });
+    image.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraBuilder;
+    use crate::environment::{GradientEnvironment, ImageEnvironment, SolidEnvironment};
+    use crate::geometry::Plane;
+    use crate::geometry::Sphere;
+    use crate::material::tests::gray;
+    use crate::material::{DiffuseLight, Lambertian, Metal};
+    use crate::vec3::Point3;
+    use std::sync::Arc;
+
+    /// A diffuse and a metal sphere on a floor under the sky
+    fn spheres() -> Vec<Box<dyn Hittable>> {
+        vec![
+            Box::new(Plane::new(Point3::zero(), Vec3::new(0., 1., 0.), gray())),
+            Box::new(Sphere::new(
+                Point3::new(-0.6, 0.5, 0.),
+                0.5,
+                Arc::new(Lambertian::new(Color::new(0.8, 0.2, 0.1))),
+            )),
+            Box::new(Sphere::new(
+                Point3::new(0.6, 0.5, 0.),
+                0.5,
+                Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.3)),
+            )),
+        ]
+    }
+
+    /// Settings for a small image that the tiles do not fit into evenly
+    fn small_settings() -> RenderSettings {
+        RenderSettings {
+            width: 13,
+            height: 7,
+            samples_per_pixel: 4,
+            max_depth: 5,
+            tile_size: 5,
+            seed: 7,
+            ..RenderSettings::default()
+        }
+    }
+
+    fn camera(settings: &RenderSettings) -> Camera {
+        let mut builder = CameraBuilder::new(Point3::new(0., 1., 3.), Point3::new(0., 0.5, 0.));
+        builder.set_aspect_ratio(settings.aspect_ratio());
+        builder.build()
+    }
+
+    /// Check that two samples are exactly equal
+    fn assert_same(a: &Sample, b: &Sample) {
+        let values = |s: &Sample| {
+            [s.color, s.albedo, s.normal]
+                .iter()
+                .flat_map(|v| [v.x, v.y, v.z])
+                .chain([s.depth])
+                .collect::<Vec<f64>>()
+        };
+        assert_eq!(values(a), values(b));
+    }
+
+    #[test]
+    fn seeded_render_does_not_depend_on_threads() {
+        let objects = spheres();
+        let world: Vec<&dyn Hittable> = objects.iter().map(|object| object.as_ref()).collect();
+        let sky = GradientEnvironment::default();
+        let settings = small_settings();
+        let camera = camera(&settings);
+
+        let single = render(
+            &world,
+            &sky,
+            &camera,
+            &RenderSettings {
+                threads: 1,
+                ..settings.clone()
+            },
+        );
+        let multi = render(
+            &world,
+            &sky,
+            &camera,
+            &RenderSettings {
+                threads: 4,
+                ..settings.clone()
+            },
+        );
+        for y in 0..settings.height {
+            for x in 0..settings.width {
+                assert_eq!(single.sample_count(x, y), settings.samples_per_pixel);
+                assert_same(&single.sample(x, y), &multi.sample(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn rerendered_pixel_matches_render() {
+        let objects = spheres();
+        let world: Vec<&dyn Hittable> = objects.iter().map(|object| object.as_ref()).collect();
+        let sky = GradientEnvironment::default();
+        let settings = small_settings();
+        let camera = camera(&settings);
+
+        let image = render(&world, &sky, &camera, &settings);
+        for (x, y) in [(0, 0), (6, 3), (12, 6)] {
+            let pixel = render_pixel(&world, &sky, &camera, &settings, x, y);
+            assert_same(&pixel, &image.sample(x, y));
+        }
+        // Another seed gives another pixel
+        let reseeded = RenderSettings {
+            seed: 8,
+            ..settings.clone()
+        };
+        let pixel = render_pixel(&world, &sky, &camera, &reseeded, 6, 3);
+        assert_ne!(pixel.color.x, image.sample(6, 3).color.x);
+    }
+
+    #[test]
+    fn lights_emit() {
+        let light = Sphere::new(
+            Point3::zero(),
+            1.,
+            Arc::new(DiffuseLight::new(Color::new(2., 3., 4.))),
+        );
+        let black = SolidEnvironment::new(Color::zero());
+        let mut sampler = Sampler::from_seed(0);
+        let ray = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+        let color = color_ray(&ray, &light, &black, 10, &mut sampler);
+        assert!((color - Color::new(2., 3., 4.)).length() < 1e-12);
+
+        // A black room around the light is lit by it and by nothing else
+        let room = Sphere::new(Point3::zero(), 10., gray());
+        let world: Vec<&dyn Hittable> = vec![&light, &room];
+        let ray = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., 1.));
+        let sample = sample_ray(&ray, &world, &black, 10, &mut sampler);
+        assert!((sample.albedo - Color::new(0.5, 0.5, 0.5)).length() < 1e-12);
+        let total = (0..100).fold(Color::zero(), |total, _| {
+            total + color_ray(&ray, &world, &black, 10, &mut sampler)
+        });
+        assert!(total.x > 0. && total.x < 100. * 2.);
+    }
+
+    #[test]
+    fn sampled_environment_is_unbiased() {
+        // Under a white sky a diffuse floor reflects its albedo
+        let sky = ImageEnvironment::new(4, 4, vec![Color::new(1., 1., 1.); 16]);
+        let floor = Plane::new(Point3::zero(), Vec3::new(0., 1., 0.), gray());
+        let mut sampler = Sampler::from_seed(0);
+        let ray = Ray::new(Point3::new(0., 1., 0.), Vec3::new(0.3, -1., 0.));
+        let count = 4000;
+        let total = (0..count).fold(Color::zero(), |total, _| {
+            total + color_ray(&ray, &floor, &sky, 10, &mut sampler)
+        });
+        assert!((total.x / count as f64 - 0.5).abs() < 0.01);
+    }
+}
@@ -0,0 +1,57 @@
+use rand::{RngCore, SeedableRng};
+use rand_pcg::Pcg32;
+
+/// Source of random numbers for rendering
+///
+/// Every camera sample gets its own sampler, seeded from the global seed, the pixel
+/// and the index of the sample. This way any sample can be reproduced exactly,
+/// independent of the order in which the samples are rendered
+pub struct Sampler {
+    rng: Pcg32,
+}
+
+impl Sampler {
+    /// Create a sampler from a seed
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+
+    /// Create the sampler for sample `index` of the pixel at (x, y)
+    pub fn for_sample(seed: u64, x: u32, y: u32, index: u32) -> Self {
+        let pixel = (x as u64) << 32 | y as u64;
+        Self::from_seed(mix_seed(mix_seed(seed, pixel), index as u64))
+    }
+}
+
+impl RngCore for Sampler {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// The SplitMix64 finalizer, scrambles the bits of a value
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Derive a new seed from a seed and a value
+fn mix_seed(seed: u64, value: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(value))
+}
@@ -0,0 +1,1226 @@
+use crate::aabb::Aabb;
+use crate::bvh::{self, BvhNode};
+use crate::camera::{Camera, CameraBuilder};
+use crate::color::Color;
+use crate::csg::Csg;
+use crate::curve::{Curve, CurveShape};
+use crate::environment::{Environment, GradientEnvironment, ImageEnvironment, SolidEnvironment};
+use crate::geometry::{
+    Capped, Cone, Cuboid, Cylinder, Disk, Plane, Sphere, Torus, Triangle, XyRect, XzRect, YzRect,
+};
+use crate::gltf;
+use crate::heightfield::Heightfield;
+use crate::hittable::{Hittable, Solid};
+use crate::implicit::{Metaball, Metaballs, Quadric};
+use crate::load::LoadError;
+use crate::material::{Dialectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::matrix::Mat4;
+use crate::mesh::{self, TriangleMesh};
+use crate::render::RenderSettings;
+use crate::sdf::{
+    Mandelbulb, Repeat, Sdf, SdfBox, SdfObject, SdfSphere, SdfTorus, SmoothUnion, Twist,
+};
+use crate::subdivision;
+use crate::texture::ImageTexture;
+use crate::transform::Transformed;
+use crate::vec3::{Point3, Vec3};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::Spanned;
+
+/// A scene that can be rendered
+pub struct Scene {
+    /// All objects in the scene
+    pub objects: Vec<Box<dyn Hittable>>,
+    /// Settings used to render the scene
+    pub settings: RenderSettings,
+    /// Camera settings, the aspect ratio is taken from the render settings
+    pub camera: CameraBuilder,
+    /// Light arriving from around the scene
+    pub environment: Box<dyn Environment>,
+}
+
+impl Scene {
+    /// The world containing all objects in the scene
+    pub fn world(&self) -> Vec<&dyn Hittable> {
+        self.objects.iter().map(|object| object.as_ref()).collect()
+    }
+
+    /// Build the camera matching the current render settings
+    pub fn camera(&self) -> Camera {
+        let mut builder = self.camera.clone();
+        builder.set_aspect_ratio(self.settings.aspect_ratio());
+        builder.build()
+    }
+}
+
+/// Error that occurs while loading a scene
+#[derive(Debug)]
+pub enum SceneError {
+    /// The scene file could not be read
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A scene file in another format could not be imported
+    Import(LoadError),
+    /// The scene description is invalid
+    Invalid {
+        /// File the description was read from, if any
+        path: Option<PathBuf>,
+        /// Line of the error, starting at 1
+        line: usize,
+        /// Column of the error, starting at 1
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => {
+                write!(f, "could not read {}: {}", path.display(), source)
+            }
+            SceneError::Import(e) => e.fmt(f),
+            SceneError::Invalid {
+                path,
+                line,
+                column,
+                message,
+            } => {
+                if let Some(path) = path {
+                    write!(f, "{}:", path.display())?;
+                }
+                write!(f, "{}:{}: {}", line, column, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Import(e) => Some(e),
+            SceneError::Invalid { .. } => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    camera: CameraDescription,
+    #[serde(default)]
+    render: RenderDescription,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDescription>,
+    /// Light around the scene, a blue sky by default
+    environment: Option<EnvironmentDescription>,
+    /// Objects that are only built once and placed in the scene by instances
+    #[serde(default)]
+    shapes: BTreeMap<String, ObjectDescription>,
+    #[serde(default)]
+    objects: Vec<ObjectDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum EnvironmentDescription {
+    Solid {
+        color: [f64; 3],
+    },
+    /// From the color straight down to the color straight up
+    Gradient {
+        bottom: [f64; 3],
+        top: [f64; 3],
+    },
+    /// An equirectangular .hdr or .exr image, with its center in the -z direction
+    Image {
+        path: Spanned<PathBuf>,
+        /// Degrees around the y axis, counter-clockwise seen from above
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+    vup: Option<[f64; 3]>,
+    vfov: Option<f64>,
+    aperture: Option<f64>,
+    focus_distance: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RenderDescription {
+    width: Option<u32>,
+    height: Option<u32>,
+    aspect_ratio: Option<f64>,
+    samples_per_pixel: Option<u32>,
+    max_depth: Option<u32>,
+    seed: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: [f64; 3],
+    },
+    Metal {
+        albedo: [f64; 3],
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dialectric {
+        refraction_index: f64,
+    },
+    /// Emits light, components above 1 are brighter than white
+    DiffuseLight {
+        color: [f64; 3],
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: Spanned<f64>,
+        material: Spanned<String>,
+    },
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        material: Spanned<String>,
+    },
+    XyRect {
+        x: [f64; 2],
+        y: [f64; 2],
+        k: f64,
+        material: Spanned<String>,
+    },
+    XzRect {
+        x: [f64; 2],
+        z: [f64; 2],
+        k: f64,
+        material: Spanned<String>,
+    },
+    YzRect {
+        y: [f64; 2],
+        z: [f64; 2],
+        k: f64,
+        material: Spanned<String>,
+    },
+    Cuboid {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: Spanned<String>,
+    },
+    Cylinder {
+        center: [f64; 3],
+        radius: Spanned<f64>,
+        height: Spanned<f64>,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: Spanned<String>,
+    },
+    Cone {
+        center: [f64; 3],
+        radius: Spanned<f64>,
+        height: Spanned<f64>,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: Spanned<String>,
+    },
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: Spanned<f64>,
+        material: Spanned<String>,
+    },
+    Torus {
+        center: [f64; 3],
+        major_radius: Spanned<f64>,
+        minor_radius: Spanned<f64>,
+        material: Spanned<String>,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        material: Spanned<String>,
+    },
+    Mesh {
+        path: Spanned<PathBuf>,
+        /// Material used for the whole mesh instead of the materials in the file
+        material: Option<Spanned<String>>,
+        /// Steps of Loop subdivision, each splits every triangle into four
+        subdivisions: Option<Spanned<u32>>,
+        displacement: Option<DisplacementDescription>,
+    },
+    /// A shape moved, rotated and scaled, which shares the geometry of the shape
+    Instance {
+        shape: Spanned<String>,
+        #[serde(default)]
+        translate: [f64; 3],
+        /// Rotation in degrees around the x, y and z axis, applied in that order
+        #[serde(default)]
+        rotate: [f64; 3],
+        scale: Option<Spanned<[f64; 3]>>,
+    },
+    Union {
+        left: Box<Spanned<ObjectDescription>>,
+        right: Box<Spanned<ObjectDescription>>,
+    },
+    Intersection {
+        left: Box<Spanned<ObjectDescription>>,
+        right: Box<Spanned<ObjectDescription>>,
+    },
+    /// The right object cut out of the left one
+    Difference {
+        left: Box<Spanned<ObjectDescription>>,
+        right: Box<Spanned<ObjectDescription>>,
+    },
+    /// The quadric surface a x² + b y² + c z² + d xy + e xz + f yz + g x + h y + i z + j
+    /// = 0, with the coefficients from a to j, cut off at the corners
+    Quadric {
+        coefficients: [f64; 10],
+        min: [f64; 3],
+        max: [f64; 3],
+        material: Spanned<String>,
+    },
+    Metaballs {
+        balls: Vec<MetaballDescription>,
+        threshold: f64,
+        material: Spanned<String>,
+    },
+    /// Terrain from a grayscale image spread between the corners
+    Heightfield {
+        path: Spanned<PathBuf>,
+        min: [f64; 3],
+        max: [f64; 3],
+        material: Spanned<String>,
+    },
+    /// A signed distance field, only searched for a surface between the corners
+    Sdf {
+        min: [f64; 3],
+        max: [f64; 3],
+        shape: SdfDescription,
+        material: Spanned<String>,
+    },
+    /// Cubic Bézier segments joined end to end, for hair, fur or grass
+    Curve {
+        /// 3 n + 1 control points for n segments
+        points: Spanned<Vec<[f64; 3]>>,
+        /// Width at the start and the end of the curve
+        width: Spanned<[f64; 2]>,
+        /// Normals at the start and the end of a flat ribbon, the curve is a round
+        /// tube without them
+        normals: Option<[[f64; 3]; 2]>,
+        material: Spanned<String>,
+    },
+}
+
+/// Moves the vertices of a mesh along their normals by the brightness of a PNG image
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DisplacementDescription {
+    texture: Spanned<PathBuf>,
+    scale: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetaballDescription {
+    center: [f64; 3],
+    radius: Spanned<f64>,
+    #[serde(default = "default_strength")]
+    strength: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum SdfDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: Spanned<f64>,
+    },
+    Cuboid {
+        min: [f64; 3],
+        max: [f64; 3],
+    },
+    Torus {
+        center: [f64; 3],
+        major_radius: Spanned<f64>,
+        minor_radius: Spanned<f64>,
+    },
+    Mandelbulb {
+        center: [f64; 3],
+        scale: Option<f64>,
+        power: Option<f64>,
+        iterations: Option<u32>,
+    },
+    SmoothUnion {
+        left: Box<SdfDescription>,
+        right: Box<SdfDescription>,
+        smoothness: f64,
+    },
+    Repeat {
+        period: [f64; 3],
+        shape: Box<SdfDescription>,
+    },
+    Twist {
+        /// Rotation in degrees per unit of height
+        rate: f64,
+        shape: Box<SdfDescription>,
+    },
+}
+
+fn default_capped() -> bool {
+    true
+}
+
+fn default_strength() -> f64 {
+    1.
+}
+
+fn default_intensity() -> f64 {
+    1.
+}
+
+impl EnvironmentDescription {
+    fn build(&self, source: &str, directory: &Path) -> Result<Box<dyn Environment>, SceneError> {
+        Ok(match self {
+            EnvironmentDescription::Solid { color } => {
+                Box::new(SolidEnvironment::new(Color::from(*color)))
+            }
+            EnvironmentDescription::Gradient { bottom, top } => Box::new(GradientEnvironment::new(
+                Color::from(*bottom),
+                Color::from(*top),
+            )),
+            EnvironmentDescription::Image {
+                path,
+                rotation,
+                intensity,
+            } => {
+                let image = ImageEnvironment::load(directory.join(path.get_ref()))
+                    .map_err(|e| invalid(source, path.span().start, e.to_string()))?;
+                Box::new(image.with_rotation(*rotation).with_intensity(*intensity))
+            }
+        })
+    }
+}
+
+impl MaterialDescription {
+    fn build(&self) -> Arc<dyn Material> {
+        match *self {
+            MaterialDescription::Lambertian { albedo } => {
+                Arc::new(Lambertian::new(Color::from(albedo)))
+            }
+            MaterialDescription::Metal { albedo, fuzz } => {
+                Arc::new(Metal::new(Color::from(albedo), fuzz))
+            }
+            MaterialDescription::Dialectric { refraction_index } => {
+                Arc::new(Dialectric::new(refraction_index))
+            }
+            MaterialDescription::DiffuseLight { color } => {
+                Arc::new(DiffuseLight::new(Color::from(color)))
+            }
+        }
+    }
+}
+
+impl SdfDescription {
+    fn build(&self, source: &str) -> Result<Box<dyn Sdf>, SceneError> {
+        Ok(match self {
+            SdfDescription::Sphere { center, radius } => Box::new(SdfSphere {
+                center: (*center).into(),
+                radius: positive(source, radius, "radius")?,
+            }),
+            SdfDescription::Cuboid { min, max } => {
+                let (min, max) = (Point3::from(*min), Point3::from(*max));
+                Box::new(SdfBox {
+                    center: 0.5 * (min + max),
+                    half_size: 0.5 * (max - min),
+                })
+            }
+            SdfDescription::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => Box::new(SdfTorus {
+                center: (*center).into(),
+                major_radius: positive(source, major_radius, "major radius")?,
+                minor_radius: positive(source, minor_radius, "minor radius")?,
+            }),
+            SdfDescription::Mandelbulb {
+                center,
+                scale,
+                power,
+                iterations,
+            } => Box::new(Mandelbulb {
+                center: (*center).into(),
+                scale: scale.unwrap_or(1.),
+                power: power.unwrap_or(8.),
+                iterations: iterations.unwrap_or(8),
+            }),
+            SdfDescription::SmoothUnion {
+                left,
+                right,
+                smoothness,
+            } => Box::new(SmoothUnion {
+                left: left.build(source)?,
+                right: right.build(source)?,
+                smoothness: *smoothness,
+            }),
+            SdfDescription::Repeat { period, shape } => Box::new(Repeat {
+                sdf: shape.build(source)?,
+                period: (*period).into(),
+            }),
+            SdfDescription::Twist { rate, shape } => Box::new(Twist {
+                sdf: shape.build(source)?,
+                rate: *rate,
+            }),
+        })
+    }
+}
+
+impl RenderDescription {
+    fn build(&self) -> RenderSettings {
+        let defaults = RenderSettings::default();
+        let width = self.width.unwrap_or(defaults.width);
+        let height = match (self.height, self.aspect_ratio) {
+            (Some(height), _) => height,
+            (None, Some(aspect_ratio)) => (width as f64 / aspect_ratio) as u32,
+            (None, None) => (width as f64 / defaults.aspect_ratio()) as u32,
+        };
+        RenderSettings {
+            width,
+            height,
+            samples_per_pixel: self.samples_per_pixel.unwrap_or(defaults.samples_per_pixel),
+            max_depth: self.max_depth.unwrap_or(defaults.max_depth),
+            seed: self.seed.unwrap_or(defaults.seed),
+            ..defaults
+        }
+    }
+}
+
+impl CameraDescription {
+    fn build(&self) -> CameraBuilder {
+        let mut builder = CameraBuilder::new(self.look_from.into(), self.look_at.into());
+        if let Some(vup) = self.vup {
+            builder.set_up_vector(&vup.into());
+        }
+        if let Some(vfov) = self.vfov {
+            builder.set_vfov(vfov);
+        }
+        if let Some(aperture) = self.aperture {
+            builder.set_aperture(aperture);
+        }
+        if let Some(focus_distance) = self.focus_distance {
+            builder.set_focus_distance(focus_distance);
+        }
+        builder
+    }
+}
+
+/// Most steps of subdivision for a mesh, each step makes four times as many triangles
+const MAX_SUBDIVISIONS: u32 = 6;
+
+/// Convert a byte offset in the source to a 1-based line and column
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn invalid(source: &str, offset: usize, message: String) -> SceneError {
+    let (line, column) = line_column(source, offset);
+    SceneError::Invalid {
+        path: None,
+        line,
+        column,
+        message,
+    }
+}
+
+/// The value of a size, which gives broken geometry unless it is positive
+fn positive(source: &str, value: &Spanned<f64>, name: &str) -> Result<f64, SceneError> {
+    let size = *value.get_ref();
+    if size > 0. {
+        Ok(size)
+    } else {
+        Err(invalid(
+            source,
+            value.span().start,
+            format!("{} must be positive", name),
+        ))
+    }
+}
+
+/// Objects built from a description
+enum Built {
+    /// A closed object, which can also be combined with CSG
+    Solid(Box<dyn Solid>),
+    /// Objects without an inside, a mesh file can contain more than one
+    Other(Vec<Box<dyn Hittable>>),
+}
+
+impl Built {
+    fn solid(solid: impl Solid + 'static) -> Self {
+        Built::Solid(Box::new(solid))
+    }
+
+    fn single(object: impl Hittable + 'static) -> Self {
+        Built::Other(vec![Box::new(object)])
+    }
+}
+
+/// Builds objects from their descriptions, sharing materials and shapes between them
+struct ObjectBuilder<'a> {
+    source: &'a str,
+    /// Directory relative paths are resolved from
+    directory: &'a Path,
+    materials: BTreeMap<&'a str, Arc<dyn Material>>,
+    shapes: BTreeMap<&'a str, Arc<dyn Hittable>>,
+}
+
+impl ObjectBuilder<'_> {
+    fn material(&self, name: &Spanned<String>) -> Result<Arc<dyn Material>, SceneError> {
+        self.materials
+            .get(name.get_ref().as_str())
+            .cloned()
+            .ok_or_else(|| {
+                invalid(
+                    self.source,
+                    name.span().start,
+                    format!("unknown material `{}`", name.get_ref()),
+                )
+            })
+    }
+
+    /// Build one side of a CSG operation, which must be a solid
+    fn solid(&self, object: &Spanned<ObjectDescription>) -> Result<Box<dyn Solid>, SceneError> {
+        match self.object(object.get_ref())? {
+            Built::Solid(solid) => Ok(solid),
+            Built::Other(_) => Err(invalid(
+                self.source,
+                object.span().start,
+                "only spheres, cuboids, tori and capped cylinders and cones can be combined".into(),
+            )),
+        }
+    }
+
+    /// Build the objects of a description, a mesh file can contain more than one
+    fn build(&self, object: &ObjectDescription) -> Result<Vec<Box<dyn Hittable>>, SceneError> {
+        Ok(match self.object(object)? {
+            Built::Solid(solid) => vec![Box::new(solid)],
+            Built::Other(objects) => objects,
+        })
+    }
+
+    /// Build a description, keeping solids apart so they can be combined with CSG
+    fn object(&self, object: &ObjectDescription) -> Result<Built, SceneError> {
+        let built = match object {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material,
+            } => Built::solid(Sphere::new(
+                (*center).into(),
+                positive(self.source, radius, "radius")?,
+                self.material(material)?,
+            )),
+            ObjectDescription::Cuboid { min, max, material } => Built::solid(Cuboid::new(
+                (*min).into(),
+                (*max).into(),
+                self.material(material)?,
+            )),
+            ObjectDescription::Cylinder {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            } => {
+                let center = (*center).into();
+                let radius = positive(self.source, radius, "radius")?;
+                let height = positive(self.source, height, "height")?;
+                let material = self.material(material)?;
+                if *capped {
+                    Built::solid(Capped::cylinder(center, radius, height, material))
+                } else {
+                    Built::single(Cylinder::new(center, radius, height, material))
+                }
+            }
+            ObjectDescription::Cone {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            } => {
+                let center = (*center).into();
+                let radius = positive(self.source, radius, "radius")?;
+                let height = positive(self.source, height, "height")?;
+                let material = self.material(material)?;
+                if *capped {
+                    Built::solid(Capped::cone(center, radius, height, material))
+                } else {
+                    Built::single(Cone::new(center, radius, height, material))
+                }
+            }
+            ObjectDescription::Torus {
+                center,
+                major_radius,
+                minor_radius,
+                material,
+            } => Built::solid(Torus::new(
+                (*center).into(),
+                positive(self.source, major_radius, "major radius")?,
+                positive(self.source, minor_radius, "minor radius")?,
+                self.material(material)?,
+            )),
+            ObjectDescription::Union { left, right } => {
+                Built::solid(Csg::union(self.solid(left)?, self.solid(right)?))
+            }
+            ObjectDescription::Intersection { left, right } => {
+                Built::solid(Csg::intersection(self.solid(left)?, self.solid(right)?))
+            }
+            ObjectDescription::Difference { left, right } => {
+                Built::solid(Csg::difference(self.solid(left)?, self.solid(right)?))
+            }
+            ObjectDescription::Plane {
+                point,
+                normal,
+                material,
+            } => Built::single(Plane::new(
+                (*point).into(),
+                (*normal).into(),
+                self.material(material)?,
+            )),
+            ObjectDescription::XyRect { x, y, k, material } => Built::single(XyRect::new(
+                x[0],
+                x[1],
+                y[0],
+                y[1],
+                *k,
+                self.material(material)?,
+            )),
+            ObjectDescription::XzRect { x, z, k, material } => Built::single(XzRect::new(
+                x[0],
+                x[1],
+                z[0],
+                z[1],
+                *k,
+                self.material(material)?,
+            )),
+            ObjectDescription::YzRect { y, z, k, material } => Built::single(YzRect::new(
+                y[0],
+                y[1],
+                z[0],
+                z[1],
+                *k,
+                self.material(material)?,
+            )),
+            ObjectDescription::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => Built::single(Disk::new(
+                (*center).into(),
+                (*normal).into(),
+                positive(self.source, radius, "radius")?,
+                self.material(material)?,
+            )),
+            ObjectDescription::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => {
+                let [a, b, c] = *vertices;
+                let mut triangle =
+                    Triangle::new([a.into(), b.into(), c.into()], self.material(material)?);
+                if let Some([a, b, c]) = *normals {
+                    triangle = triangle.with_normals([a.into(), b.into(), c.into()]);
+                }
+                if let Some([a, b, c]) = *uvs {
+                    triangle = triangle.with_uvs([(a[0], a[1]), (b[0], b[1]), (c[0], c[1])]);
+                }
+                Built::single(triangle)
+            }
+            ObjectDescription::Mesh {
+                path,
+                material,
+                subdivisions,
+                displacement,
+            } => {
+                let subdivisions = match subdivisions {
+                    Some(steps) if *steps.get_ref() > MAX_SUBDIVISIONS => {
+                        return Err(invalid(
+                            self.source,
+                            steps.span().start,
+                            format!("subdivisions must be at most {}", MAX_SUBDIVISIONS),
+                        ))
+                    }
+                    Some(steps) => *steps.get_ref(),
+                    None => 0,
+                };
+                let material = material
+                    .as_ref()
+                    .map(|name| self.material(name))
+                    .transpose()?;
+                let mut meshes = mesh::load_mesh(self.directory.join(path.get_ref()), material)
+                    .map_err(|e| invalid(self.source, path.span().start, e.to_string()))?;
+                let displacement = displacement
+                    .as_ref()
+                    .map(|displacement| {
+                        let texture = &displacement.texture;
+                        ImageTexture::load_png(self.directory.join(texture.get_ref()))
+                            .map(|image| (image, displacement.scale))
+                            .map_err(|e| invalid(self.source, texture.span().start, e.to_string()))
+                    })
+                    .transpose()?;
+                if subdivisions > 0 || displacement.is_some() {
+                    meshes = meshes
+                        .into_iter()
+                        .map(|mesh| {
+                            let mut data = (**mesh.data()).clone();
+                            for _ in 0..subdivisions {
+                                data = subdivision::loop_subdivide(&data);
+                            }
+                            if let Some((image, scale)) = &displacement {
+                                data = subdivision::displace(&data, image, *scale);
+                            }
+                            TriangleMesh::new(Arc::new(data), mesh.material().clone())
+                                .map_err(|e| invalid(self.source, path.span().start, e.to_string()))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                Built::Other(
+                    meshes
+                        .into_iter()
+                        .map(|mesh| Box::new(mesh) as Box<dyn Hittable>)
+                        .collect(),
+                )
+            }
+            ObjectDescription::Instance {
+                shape,
+                translate,
+                rotate,
+                scale,
+            } => {
+                let object = self.shapes.get(shape.get_ref().as_str()).ok_or_else(|| {
+                    invalid(
+                        self.source,
+                        shape.span().start,
+                        format!("unknown shape `{}`", shape.get_ref()),
+                    )
+                })?;
+                let [x, y, z] = *rotate;
+                let mut transform = Mat4::translation(&(*translate).into())
+                    * Mat4::rotation_z(z)
+                    * Mat4::rotation_y(y)
+                    * Mat4::rotation_x(x);
+                if let Some(scale) = scale {
+                    if scale.get_ref().contains(&0.) {
+                        return Err(invalid(
+                            self.source,
+                            scale.span().start,
+                            "scale must not be zero".into(),
+                        ));
+                    }
+                    transform = transform * Mat4::scaling(&(*scale.get_ref()).into());
+                }
+                Built::single(Transformed::new(object.clone(), transform))
+            }
+            ObjectDescription::Quadric {
+                coefficients,
+                min,
+                max,
+                material,
+            } => Built::single(Quadric::new(
+                *coefficients,
+                Some(Aabb::new((*min).into(), (*max).into())),
+                self.material(material)?,
+            )),
+            ObjectDescription::Metaballs {
+                balls,
+                threshold,
+                material,
+            } => {
+                let balls = balls
+                    .iter()
+                    .map(|ball| {
+                        Ok(Metaball {
+                            center: ball.center.into(),
+                            radius: positive(self.source, &ball.radius, "radius")?,
+                            strength: ball.strength,
+                        })
+                    })
+                    .collect::<Result<_, SceneError>>()?;
+                Built::single(Metaballs::new(balls, *threshold, self.material(material)?))
+            }
+            ObjectDescription::Heightfield {
+                path,
+                min,
+                max,
+                material,
+            } => {
+                let bounds = Aabb::new((*min).into(), (*max).into());
+                let heightfield = Heightfield::load(
+                    self.directory.join(path.get_ref()),
+                    bounds,
+                    self.material(material)?,
+                )
+                .map_err(|e| invalid(self.source, path.span().start, e.to_string()))?;
+                Built::single(heightfield)
+            }
+            ObjectDescription::Sdf {
+                min,
+                max,
+                shape,
+                material,
+            } => Built::single(SdfObject::new(
+                shape.build(self.source)?,
+                Aabb::new((*min).into(), (*max).into()),
+                self.material(material)?,
+            )),
+            ObjectDescription::Curve {
+                points,
+                width,
+                normals,
+                material,
+            } => {
+                let count = points.get_ref().len();
+                if count < 4 || !(count - 1).is_multiple_of(3) {
+                    return Err(invalid(
+                        self.source,
+                        points.span().start,
+                        "curve needs 3 n + 1 control points".into(),
+                    ));
+                }
+                // The curve may taper to a point at one end
+                let [start, end] = *width.get_ref();
+                if start < 0. || end < 0. || start.max(end) <= 0. {
+                    return Err(invalid(
+                        self.source,
+                        width.span().start,
+                        "width must not be negative and can only be zero at one end".into(),
+                    ));
+                }
+                let points: Vec<Point3> = points.get_ref().iter().map(|&p| p.into()).collect();
+                let shape = match normals {
+                    Some([start, end]) => CurveShape::Ribbon {
+                        normals: [(*start).into(), (*end).into()],
+                    },
+                    None => CurveShape::Cylinder,
+                };
+                Built::Other(
+                    Curve::spline(&points, [start, end], shape, self.material(material)?)
+                        .into_iter()
+                        .map(|segment| Box::new(segment) as Box<dyn Hittable>)
+                        .collect(),
+                )
+            }
+        };
+        Ok(built)
+    }
+}
+
+/// Parse a scene from a TOML scene description
+///
+/// Relative paths in the description are resolved from the working directory
+pub fn parse_scene(source: &str) -> Result<Scene, SceneError> {
+    parse_scene_in(source, Path::new(""))
+}
+
+/// Parse a scene, resolving relative paths from the given directory
+fn parse_scene_in(source: &str, directory: &Path) -> Result<Scene, SceneError> {
+    let description: SceneDescription = toml::from_str(source).map_err(|e| {
+        let offset = e.span().map(|span| span.start).unwrap_or(0);
+        invalid(source, offset, e.message().to_string())
+    })?;
+
+    // Create every material once so objects can share them
+    let materials = description
+        .materials
+        .iter()
+        .map(|(name, material)| (name.as_str(), material.build()))
+        .collect();
+    let mut builder = ObjectBuilder {
+        source,
+        directory,
+        materials,
+        shapes: BTreeMap::new(),
+    };
+    for (name, shape) in &description.shapes {
+        if let ObjectDescription::Instance { shape, .. } = shape {
+            return Err(invalid(
+                source,
+                shape.span().start,
+                "an instance can not be used as a shape".into(),
+            ));
+        }
+        let mut parts = builder.build(shape)?;
+        let shape: Arc<dyn Hittable> = if parts.len() == 1 {
+            Arc::from(parts.pop().unwrap())
+        } else {
+            Arc::new(BvhNode::new(parts))
+        };
+        builder.shapes.insert(name.as_str(), shape);
+    }
+
+    let environment = match &description.environment {
+        Some(environment) => environment.build(source, directory)?,
+        None => Box::new(GradientEnvironment::default()),
+    };
+
+    let mut objects = Vec::new();
+    for object in &description.objects {
+        objects.append(&mut builder.build(object)?);
+    }
+
+    Ok(Scene {
+        objects: bvh::accelerate(objects),
+        settings: description.render.build(),
+        camera: description.camera.build(),
+        environment,
+    })
+}
+
+/// Load a glTF file as a scene with the default render settings
+///
+/// The camera and aspect ratio are taken from the file. Without a camera the scene is
+/// viewed from the front, far enough away to see all of it
+fn load_gltf_scene(path: &Path) -> Result<Scene, SceneError> {
+    let gltf = gltf::load_gltf(path, None).map_err(SceneError::Import)?;
+    let objects: Vec<Box<dyn Hittable>> = gltf
+        .meshes
+        .into_iter()
+        .map(|mesh| Box::new(mesh) as Box<dyn Hittable>)
+        .collect();
+
+    let mut settings = RenderSettings::default();
+    if let Some(aspect_ratio) = gltf.aspect_ratio {
+        settings.height = ((settings.width as f64 / aspect_ratio) as u32).max(1);
+    }
+    let camera = gltf.camera.unwrap_or_else(|| {
+        let bbox = objects
+            .iter()
+            .filter_map(|object| object.bounding_box())
+            .fold(Aabb::empty(), |bbox, other| bbox.surrounding(&other));
+        let (center, radius) = if bbox.min.x <= bbox.max.x {
+            (bbox.centroid(), (bbox.max - bbox.min).length() / 2.)
+        } else {
+            (Point3::zero(), 1.)
+        };
+        let vfov: f64 = 40.;
+        let distance = radius / (vfov / 2.).to_radians().tan();
+        let mut builder = CameraBuilder::new(center + Vec3::new(0., 0., distance), center);
+        builder.set_vfov(vfov);
+        builder.set_aperture(0.);
+        builder
+    });
+
+    Ok(Scene {
+        objects: bvh::accelerate(objects),
+        settings,
+        camera,
+        environment: Box::new(GradientEnvironment::default()),
+    })
+}
+
+/// Load a scene from a TOML scene description file or a glTF file (.gltf or .glb)
+///
+/// Relative paths in the description are resolved from the directory of the file
+pub fn load_scene(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    if let Some("gltf") | Some("glb") = extension.as_deref() {
+        return load_gltf_scene(path);
+    }
+    let source = std::fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_scene_in(&source, directory).map_err(|e| match e {
+        SceneError::Invalid {
+            line,
+            column,
+            message,
+            ..
+        } => SceneError::Invalid {
+            path: Some(path.to_path_buf()),
+            line,
+            column,
+            message,
+        },
+        e => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    const CAMERA: &str = "[camera]\nlook_from = [0.0, 0.0, 0.0]\nlook_at = [0.0, 0.0, -1.0]\n";
+
+    /// The message of the error a scene with the given objects fails with
+    fn error(objects: &str) -> String {
+        let source = format!("{}{}", CAMERA, objects);
+        match parse_scene(&source) {
+            Ok(_) => panic!("scene should be invalid"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_minimal_scene() {
+        let source = format!(
+            "{}{}",
+            CAMERA,
+            r#"
+[render]
+width = 40
+height = 20
+
+[materials.red]
+lambertian = { albedo = [0.8, 0.1, 0.1] }
+
+[[objects]]
+sphere = { center = [0.0, 0.0, -1.0], radius = 0.5, material = "red" }
+"#
+        );
+        let scene = parse_scene(&source).unwrap();
+        assert_eq!((scene.settings.width, scene.settings.height), (40, 20));
+        assert_eq!(scene.objects.len(), 1);
+        let ray = Ray::new(Point3::zero(), Vec3::new(0., 0., -1.));
+        let world = scene.world();
+        let hit = world.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reports_unknown_material() {
+        let message = error(
+            r#"
+[[objects]]
+sphere = { center = [0.0, 0.0, -1.0], radius = 0.5, material = "gold" }
+"#,
+        );
+        assert_eq!(message, "6:64: unknown material `gold`");
+    }
+
+    #[test]
+    fn reports_malformed_vector() {
+        let message = error(
+            r#"
+[materials.red]
+lambertian = { albedo = [0.8, 0.1] }
+"#,
+        );
+        assert_eq!(
+            message,
+            "6:25: invalid length 2, expected an array of length 3"
+        );
+    }
+
+    #[test]
+    fn reports_unknown_object() {
+        let message = error(
+            r#"
+[[objects]]
+cube = { center = [0.0, 0.0, -1.0], size = 1.0 }
+"#,
+        );
+        assert!(message.starts_with("6:1: unknown variant `cube`, expected one of `sphere`"));
+    }
+
+    #[test]
+    fn rejects_sizes_that_are_not_positive() {
+        let message = error(
+            r#"
+[materials.red]
+lambertian = { albedo = [0.8, 0.1, 0.1] }
+
+[[objects]]
+torus = { center = [0.0, 0.0, -1.0], major_radius = 1.0, minor_radius = 0.0, material = "red" }
+"#,
+        );
+        assert_eq!(message, "9:73: minor radius must be positive");
+        let message = error(
+            r#"
+[materials.red]
+lambertian = { albedo = [0.8, 0.1, 0.1] }
+
+[[objects]]
+sphere = { center = [0.0, 0.0, -1.0], radius = -0.5, material = "red" }
+"#,
+        );
+        assert_eq!(message, "9:48: radius must be positive");
+        let message = error(
+            r#"
+[materials.red]
+lambertian = { albedo = [0.8, 0.1, 0.1] }
+
+[[objects]]
+curve = { points = [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 2.0, 0.0], [0.0, 3.0, 0.0]], width = [0.0, 0.0], material = "red" }
+"#,
+        );
+        assert_eq!(
+            message,
+            "9:98: width must not be negative and can only be zero at one end"
+        );
+    }
+
+    #[test]
+    fn rejects_too_many_subdivisions() {
+        let message = error(
+            r#"
+[[objects]]
+mesh = { path = "scenes/models/cube.obj", subdivisions = 12 }
+"#,
+        );
+        assert_eq!(message, "6:58: subdivisions must be at most 6");
+    }
+
+    #[test]
+    fn rejects_open_csg_operands() {
+        let message = error(
+            r#"
+[materials.red]
+lambertian = { albedo = [0.8, 0.1, 0.1] }
+
+[[objects]]
+[objects.union]
+left = { sphere = { center = [0.0, 0.0, -1.0], radius = 0.5, material = "red" } }
+right = { cylinder = { center = [0.0, 0.0, -1.0], radius = 0.2, height = 1.0, capped = false, material = "red" } }
+"#,
+        );
+        assert_eq!(
+            message,
+            "11:9: only spheres, cuboids, tori and capped cylinders and cones can be combined"
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use std::sync::Arc;
+
+/// Distance below which a point counts as on the surface
+const SURFACE_DISTANCE: f64 = 1e-4;
+/// Maximum number of steps along a ray before giving up
+const MAX_STEPS: usize = 512;
+/// Offset of the samples used to estimate the normal
+const NORMAL_OFFSET: f64 = 1e-5;
+
+/// A signed distance field, negative inside of the shape
+///
+/// The distance may underestimate the distance to the surface, but must never
+/// overestimate it, or the ray marching can step through the surface
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: &Point3) -> f64;
+}
+
+impl<F: Fn(&Point3) -> f64 + Send + Sync> Sdf for F {
+    fn distance(&self, p: &Point3) -> f64 {
+        self(p)
+    }
+}
+
+pub struct SdfSphere {
+    pub center: Point3,
+    pub radius: f64,
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Point3) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+}
+
+/// An axis-aligned box
+pub struct SdfBox {
+    pub center: Point3,
+    /// Half of the size of the box along each axis
+    pub half_size: Vec3,
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: &Point3) -> f64 {
+        let offset = p - self.center;
+        let q = Vec3::new(
+            offset.x.abs() - self.half_size.x,
+            offset.y.abs() - self.half_size.y,
+            offset.z.abs() - self.half_size.z,
+        );
+        let outside = q.max(&Vec3::zero()).length();
+        let inside = q.x.max(q.y).max(q.z).min(0.);
+        outside + inside
+    }
+}
+
+/// A torus lying in the xz plane
+pub struct SdfTorus {
+    pub center: Point3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: &Point3) -> f64 {
+        let offset = p - self.center;
+        let ring = (offset.x * offset.x + offset.z * offset.z).sqrt() - self.major_radius;
+        (ring * ring + offset.y * offset.y).sqrt() - self.minor_radius
+    }
+}
+
+/// The Mandelbulb fractal, using its distance estimator
+pub struct Mandelbulb {
+    pub center: Point3,
+    /// Size of the fractal, which fits in a sphere of about 1.2 times the scale
+    pub scale: f64,
+    /// Power of the iterated formula, 8 gives the classic shape
+    pub power: f64,
+    /// More iterations give more detail
+    pub iterations: u32,
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: &Point3) -> f64 {
+        let c = (p - self.center) / self.scale;
+        let mut z = c;
+        // Derivative of the iteration, used to estimate the distance
+        let mut dr = 1.;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            if r > 2. || r == 0. {
+                break;
+            }
+            // Raise z to the power in spherical coordinates
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.) * self.power * dr + 1.;
+            let length = r.powf(self.power);
+            z = length
+                * Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+                + c;
+            r = z.length();
+        }
+        if r == 0. {
+            return 0.;
+        }
+        0.5 * r.ln() * r / dr * self.scale
+    }
+}
+
+/// Union of two fields that blends them together where they meet
+pub struct SmoothUnion {
+    pub left: Box<dyn Sdf>,
+    pub right: Box<dyn Sdf>,
+    /// Distance over which the shapes are blended, 0 gives a sharp union
+    pub smoothness: f64,
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: &Point3) -> f64 {
+        let (a, b) = (self.left.distance(p), self.right.distance(p));
+        if self.smoothness <= 0. {
+            return a.min(b);
+        }
+        // Polynomial smooth minimum
+        let h = (0.5 + 0.5 * (b - a) / self.smoothness).clamp(0., 1.);
+        b + (a - b) * h - self.smoothness * h * (1. - h)
+    }
+}
+
+/// A field repeated infinitely in a grid
+///
+/// The shape should fit in the cell of the grid around the origin, which spans half a
+/// period in each direction
+pub struct Repeat {
+    pub sdf: Box<dyn Sdf>,
+    /// Size of a cell along each axis, 0 does not repeat along that axis
+    pub period: Vec3,
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: &Point3) -> f64 {
+        let wrap = |x: f64, period: f64| {
+            if period > 0. {
+                x - period * (x / period).round()
+            } else {
+                x
+            }
+        };
+        let q = Point3::new(
+            wrap(p.x, self.period.x),
+            wrap(p.y, self.period.y),
+            wrap(p.z, self.period.z),
+        );
+        self.sdf.distance(&q)
+    }
+}
+
+/// A field twisted around the y axis
+pub struct Twist {
+    pub sdf: Box<dyn Sdf>,
+    /// Rotation in degrees per unit of height
+    pub rate: f64,
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: &Point3) -> f64 {
+        let rate = self.rate.to_radians();
+        let (sin, cos) = (rate * p.y).sin_cos();
+        let q = Point3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+        // The twist stretches space further away from the axis, so the distance is
+        // scaled down to stay a safe step
+        let radius = (p.x * p.x + p.z * p.z).sqrt();
+        self.sdf.distance(&q) / (1. + (rate * radius).powi(2)).sqrt()
+    }
+}
+
+/// An object given by a signed distance field, rendered by sphere tracing
+///
+/// The ray steps forward by the distance to the nearest surface until it is close
+/// enough to count as a hit. Only the part of the ray inside the bounds is searched
+pub struct SdfObject {
+    pub sdf: Box<dyn Sdf>,
+    pub bounds: Aabb,
+    pub material: Arc<dyn Material>,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Box<dyn Sdf>, bounds: Aabb, material: Arc<dyn Material>) -> Self {
+        Self {
+            sdf,
+            bounds,
+            material,
+        }
+    }
+
+    /// Outward normal from the gradient of the field, using central differences
+    fn normal(&self, p: &Point3) -> Vec3 {
+        let difference =
+            |offset: Vec3| self.sdf.distance(&(*p + offset)) - self.sdf.distance(&(p - offset));
+        Vec3::new(
+            difference(Vec3::new(NORMAL_OFFSET, 0., 0.)),
+            difference(Vec3::new(0., NORMAL_OFFSET, 0.)),
+            difference(Vec3::new(0., 0., NORMAL_OFFSET)),
+        )
+        .unit_vector()
+    }
+
+    /// Find where the field changes sign between t0 and t1 by bisection
+    fn refine(&self, r: &Ray, mut t0: f64, mut t1: f64, d0: f64) -> f64 {
+        for _ in 0..32 {
+            let t = 0.5 * (t0 + t1);
+            if (self.sdf.distance(&r.at(t)) < 0.) == (d0 < 0.) {
+                t0 = t;
+            } else {
+                t1 = t;
+            }
+        }
+        0.5 * (t0 + t1)
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (start, end) = self.bounds.intersect(r, t_min, t_max)?;
+        // Distances are measured along the ray, which need not have unit length
+        let speed = r.dir.length();
+        let mut t = start;
+        let mut previous = (t, self.sdf.distance(&r.at(t)));
+        // A ray that starts on the surface, e.g. after a reflection, must leave it before
+        // it can hit it again
+        let mut leaving = previous.1.abs() < SURFACE_DISTANCE;
+        for _ in 0..MAX_STEPS {
+            let (previous_t, previous_distance) = previous;
+            t += previous_distance.abs().max(SURFACE_DISTANCE) / speed;
+            if t > end {
+                return None;
+            }
+            let distance = self.sdf.distance(&r.at(t));
+            let hit_t = if (distance < 0.) != (previous_distance < 0.) {
+                // Stepped through the surface
+                Some(self.refine(r, previous_t, t, previous_distance))
+            } else if distance.abs() < SURFACE_DISTANCE && !leaving {
+                Some(t)
+            } else {
+                None
+            };
+            if let Some(t) = hit_t {
+                let p = r.at(t);
+                let normal = self.normal(&p);
+                return Some(HitRecord::new(r, p, &normal, t, &*self.material));
+            }
+            leaving &= distance.abs() < SURFACE_DISTANCE;
+            previous = (t, distance);
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::gray;
+
+    fn object(sdf: Box<dyn Sdf>) -> SdfObject {
+        let extent = Vec3::new(3., 3., 3.);
+        let material = gray();
+        SdfObject::new(sdf, Aabb::new(-extent, extent), material)
+    }
+
+    #[test]
+    fn matches_analytic_sphere() {
+        let sphere = object(Box::new(SdfSphere {
+            center: Point3::zero(),
+            radius: 1.,
+        }));
+        let ray = Ray::new(Point3::new(0.3, 0., 5.), Vec3::new(0., 0., -2.));
+        let hit = sphere.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        let expected_z = (1. - 0.3 * 0.3_f64).sqrt();
+        assert!((hit.p.z - expected_z).abs() < 1e-3);
+        assert!((hit.normal - hit.p).length() < 1e-3);
+        assert!(hit.front_face);
+
+        // From inside the ray hits the back of the sphere
+        let inside = Ray::new(hit.p, ray.dir);
+        let hit = sphere.hit(&inside, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.p.z + expected_z).abs() < 1e-3);
+        assert!(!hit.front_face);
+
+        let miss = Ray::new(Point3::new(1.1, 0., 5.), Vec3::new(0., 0., -1.));
+        assert!(sphere.hit(&miss, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn closures_are_fields() {
+        // A plane at y = 0.5, limited by the bounds
+        let plane = object(Box::new(|p: &Point3| p.y - 0.5));
+        let ray = Ray::new(Point3::new(0., 2., 0.), Vec3::new(0., -1., 0.));
+        let hit = plane.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-3);
+        assert!((hit.normal.y - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn combinators() {
+        let sphere = |x: f64| -> Box<dyn Sdf> {
+            Box::new(SdfSphere {
+                center: Point3::new(x, 0., 0.),
+                radius: 0.5,
+            })
+        };
+        let p = Point3::new(0., 0., 0.);
+
+        let union = SmoothUnion {
+            left: sphere(-0.6),
+            right: sphere(0.6),
+            smoothness: 0.5,
+        };
+        // A sharp union would leave a gap of 0.1 between the spheres
+        assert!(union.distance(&p) < 0.);
+
+        let repeated = Repeat {
+            sdf: sphere(0.),
+            period: Vec3::new(2., 0., 0.),
+        };
+        let q = Point3::new(10., 0., 0.);
+        assert!((repeated.distance(&q) + 0.5).abs() < 1e-12);
+
+        // A twisted box gets a safe distance that is never more than the true one
+        let twisted = Twist {
+            sdf: Box::new(SdfBox {
+                center: Point3::zero(),
+                half_size: Vec3::new(1., 2., 0.2),
+            }),
+            rate: 1f64.to_degrees(),
+        };
+        let q = Point3::new(2., 0., 0.);
+        assert!((twisted.distance(&q) - 1. / 5f64.sqrt()).abs() < 1e-12);
+    }
+}
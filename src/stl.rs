@@ -0,0 +1,147 @@
+use crate::load::{self, LoadError};
+use crate::mesh::MeshData;
+use crate::vec3::Point3;
+use std::convert::TryInto;
+use std::path::Path;
+
+/// Size of the header of a binary file, followed by the number of triangles
+const HEADER_SIZE: usize = 80;
+/// Size of a triangle in a binary file: a normal, three vertices and an attribute
+const TRIANGLE_SIZE: usize = 50;
+
+/// Check whether the data is a binary STL file
+///
+/// ASCII files start with `solid`, but so do some binary files. Those are recognized by
+/// the file size matching the triangle count in the header
+fn is_binary(data: &[u8]) -> bool {
+    if data.len() >= HEADER_SIZE + 4 {
+        let count = u32::from_le_bytes(data[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap());
+        if data.len() == HEADER_SIZE + 4 + count as usize * TRIANGLE_SIZE {
+            return true;
+        }
+    }
+    !data.starts_with(b"solid")
+}
+
+fn parse_binary(data: &[u8]) -> Result<Vec<Point3>, String> {
+    if data.len() < HEADER_SIZE + 4 {
+        return Err("file is too short".into());
+    }
+    let count = u32::from_le_bytes(data[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap());
+    let triangles = &data[HEADER_SIZE + 4..];
+    if triangles.len() < count as usize * TRIANGLE_SIZE {
+        return Err(format!("file is too short for {} triangles", count));
+    }
+
+    let read = |bytes: &[u8]| f32::from_le_bytes(bytes.try_into().unwrap()) as f64;
+    Ok(triangles
+        .chunks_exact(TRIANGLE_SIZE)
+        .take(count as usize)
+        .flat_map(|triangle| {
+            // Skip the normal, the vertices follow it
+            triangle[12..48]
+                .chunks_exact(12)
+                .map(move |v| Point3::new(read(&v[0..4]), read(&v[4..8]), read(&v[8..12])))
+        })
+        .collect())
+}
+
+fn parse_ascii(data: &[u8]) -> Result<Vec<Point3>, String> {
+    let source = std::str::from_utf8(data).map_err(|_| "file is not valid text")?;
+    let mut tokens = source.split_ascii_whitespace();
+    let mut vertices = Vec::new();
+    while let Some(token) = tokens.next() {
+        // The normals are ignored, only the vertices are needed
+        if token != "vertex" {
+            continue;
+        }
+        let mut coordinate = || {
+            let token = tokens.next().ok_or("unexpected end of file")?;
+            token
+                .parse()
+                .map_err(|_| format!("invalid coordinate `{}`", token))
+        };
+        vertices.push(Point3::new(coordinate()?, coordinate()?, coordinate()?));
+    }
+    if vertices.len() % 3 != 0 {
+        return Err("facet without three vertices".into());
+    }
+    Ok(vertices)
+}
+
+/// Parse an ASCII or binary STL file
+fn parse_stl(data: &[u8]) -> Result<MeshData, String> {
+    let positions = if is_binary(data) {
+        parse_binary(data)?
+    } else {
+        parse_ascii(data)?
+    };
+    let triangles = (0..positions.len() as u32 / 3)
+        .map(|i| [3 * i, 3 * i + 1, 3 * i + 2])
+        .collect();
+    Ok(MeshData {
+        positions,
+        triangles,
+        ..MeshData::default()
+    })
+}
+
+/// Load a mesh from an ASCII or binary STL file
+///
+/// STL files only contain flat triangles, the normals stored in the file are ignored
+/// and the front of every triangle is taken from the order of its vertices
+pub fn load_stl(path: impl AsRef<Path>) -> Result<MeshData, LoadError> {
+    let path = path.as_ref();
+    parse_stl(&load::read_file(path)?).map_err(|message| LoadError {
+        path: Some(path.to_path_buf()),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ascii() {
+        let source = "solid triangle
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1.5e0 0
+    endloop
+  endfacet
+endsolid triangle
+";
+        let mesh = parse_stl(source.as_bytes()).unwrap();
+        assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+        assert_eq!(mesh.positions[1].x, 1.);
+        assert_eq!(mesh.positions[2].y, 1.5);
+    }
+
+    #[test]
+    fn parses_binary_starting_with_solid() {
+        let mut data = b"solid but actually binary".to_vec();
+        data.resize(HEADER_SIZE, 0);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for triangle in 0..2 {
+            data.extend_from_slice(&[0; 12]);
+            for vertex in 0..3 {
+                for axis in 0..3 {
+                    let value = (triangle * 9 + vertex * 3 + axis) as f32;
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            data.extend_from_slice(&[0; 2]);
+        }
+        let mesh = parse_stl(&data).unwrap();
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [3, 4, 5]]);
+        assert_eq!(mesh.positions[4].x, 12.);
+        assert_eq!(mesh.positions[5].z, 17.);
+
+        data.truncate(data.len() - 1);
+        data[0] = b'x';
+        assert!(parse_stl(&data).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::color;
+use crate::mesh::MeshData;
+use crate::texture::Texture;
+use crate::vec3::{Point3, Vec3};
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::PI;
+
+/// Give vertices with the same position the same point index
+///
+/// Meshes split vertices where the normals or texture coordinates differ, but the
+/// surface must stay connected there. Returns the point of every vertex and the
+/// number of points
+fn weld(positions: &[Point3]) -> (Vec<usize>, usize) {
+    let mut points = HashMap::new();
+    let point_of = positions
+        .iter()
+        .map(|p| {
+            let key = [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+            let next = points.len();
+            *points.entry(key).or_insert(next)
+        })
+        .collect();
+    (point_of, points.len())
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Refine a triangle mesh with one step of Loop subdivision
+///
+/// Every triangle is split into four, and all vertices are moved so that repeated
+/// subdivision converges to a smooth surface. Edges with only one triangle are kept
+/// as boundary curves. Texture coordinates and colors are interpolated linearly, and
+/// the mesh gets smooth normals
+pub fn loop_subdivide(mesh: &MeshData) -> MeshData {
+    let (point_of, point_count) = weld(&mesh.positions);
+    let mut points = vec![Point3::zero(); point_count];
+    for (vertex, &point) in point_of.iter().enumerate() {
+        points[point] = mesh.positions[vertex];
+    }
+
+    // The points opposite of every edge, one for each triangle next to it
+    let mut edges: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for triangle in &mesh.triangles {
+        let [a, b, c] = [0, 1, 2].map(|i| point_of[triangle[i] as usize]);
+        for (a, b, opposite) in [(a, b, c), (b, c, a), (c, a, b)] {
+            edges.entry(edge_key(a, b)).or_default().push(opposite);
+        }
+    }
+    let mut neighbours = vec![Vec::new(); point_count];
+    let mut boundary = vec![Vec::new(); point_count];
+    for (&(a, b), opposite) in &edges {
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+        if opposite.len() != 2 {
+            boundary[a].push(b);
+            boundary[b].push(a);
+        }
+    }
+
+    let moved: Vec<Point3> = (0..point_count)
+        .map(|i| {
+            let p = points[i];
+            match (boundary[i].as_slice(), neighbours[i].len()) {
+                ([], n) if n >= 3 => {
+                    let n = n as f64;
+                    let beta = (5. / 8. - (3. / 8. + (2. * PI / n).cos() / 4.).powi(2)) / n;
+                    let sum = neighbours[i]
+                        .iter()
+                        .fold(Vec3::zero(), |sum, &j| sum + points[j]);
+                    (1. - n * beta) * p + beta * sum
+                }
+                (&[a, b], _) => 0.75 * p + 0.125 * (points[a] + points[b]),
+                // Corners and points where more than two boundaries meet stay in place
+                _ => p,
+            }
+        })
+        .collect();
+    let edge_point = |a: usize, b: usize| match edges[&edge_key(a, b)].as_slice() {
+        &[c, d] => 0.375 * (points[a] + points[b]) + 0.125 * (points[c] + points[d]),
+        _ => 0.5 * (points[a] + points[b]),
+    };
+
+    let mut result = MeshData {
+        positions: point_of.iter().map(|&point| moved[point]).collect(),
+        normals: Vec::new(),
+        uvs: mesh.uvs.clone(),
+        colors: mesh.colors.clone(),
+        triangles: Vec::with_capacity(4 * mesh.triangles.len()),
+    };
+    // A new vertex on every edge between two vertices, shared by the triangles on
+    // both sides unless the vertices are split there
+    let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut midpoint = |result: &mut MeshData, a: u32, b: u32| {
+        let key = if a < b { (a, b) } else { (b, a) };
+        *midpoints.entry(key).or_insert_with(|| {
+            let (a, b) = (a as usize, b as usize);
+            result.positions.push(edge_point(point_of[a], point_of[b]));
+            if !mesh.uvs.is_empty() {
+                let ((ua, va), (ub, vb)) = (mesh.uvs[a], mesh.uvs[b]);
+                result.uvs.push((0.5 * (ua + ub), 0.5 * (va + vb)));
+            }
+            if !mesh.colors.is_empty() {
+                result.colors.push(0.5 * (mesh.colors[a] + mesh.colors[b]));
+            }
+            (result.positions.len() - 1) as u32
+        })
+    };
+    for &[a, b, c] in &mesh.triangles {
+        let ab = midpoint(&mut result, a, b);
+        let bc = midpoint(&mut result, b, c);
+        let ca = midpoint(&mut result, c, a);
+        result
+            .triangles
+            .extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+    }
+    smooth_normals(&mut result);
+    result
+}
+
+/// Replace the normals of a mesh by the average of the normals of the triangles
+/// around each vertex, weighted by their area
+///
+/// Vertices with the same position get the same normal
+pub fn smooth_normals(mesh: &mut MeshData) {
+    let (point_of, point_count) = weld(&mesh.positions);
+    let mut normals = vec![Vec3::zero(); point_count];
+    for &[a, b, c] in &mesh.triangles {
+        let [p0, p1, p2] = [a, b, c].map(|i| mesh.positions[i as usize]);
+        // The length of the cross product is twice the area
+        let normal = (p1 - p0).cross(&(p2 - p0));
+        for i in [a, b, c] {
+            normals[point_of[i as usize]] += normal;
+        }
+    }
+    mesh.normals = point_of
+        .iter()
+        .map(|&point| {
+            let normal = normals[point];
+            if normal.length_squared() > 0. {
+                normal.unit_vector()
+            } else {
+                normal
+            }
+        })
+        .collect();
+}
+
+/// Move the vertices of a mesh along their normals by the luminance of a texture
+/// times the scale
+///
+/// The texture is looked up at the texture coordinates of the vertices, so the mesh
+/// should be finely subdivided for the detail of the texture to show. Vertices with
+/// the same position are moved together, so the surface does not tear apart at seams
+/// in the texture coordinates. Meshes without normals get smooth normals first, and
+/// the result gets new smooth normals
+pub fn displace(mesh: &MeshData, texture: &dyn Texture, scale: f64) -> MeshData {
+    let mut result = mesh.clone();
+    if result.normals.is_empty() {
+        smooth_normals(&mut result);
+    }
+    let (point_of, point_count) = weld(&result.positions);
+    let mut offsets = vec![(Vec3::zero(), 0); point_count];
+    for (vertex, &point) in point_of.iter().enumerate() {
+        let p = &result.positions[vertex];
+        let (u, v) = result.uvs.get(vertex).copied().unwrap_or((0., 0.));
+        let height = color::luminance(&texture.value(u, v, p));
+        let (sum, count) = &mut offsets[point];
+        *sum += result.normals[vertex] * (height * scale);
+        *count += 1;
+    }
+    for (vertex, &point) in point_of.iter().enumerate() {
+        let (sum, count) = offsets[point];
+        result.positions[vertex] += sum / count as f64;
+    }
+    smooth_normals(&mut result);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::texture::SolidColor;
+
+    /// A regular tetrahedron centered at the origin
+    fn tetrahedron() -> MeshData {
+        MeshData {
+            positions: vec![
+                Point3::new(1., 1., 1.),
+                Point3::new(1., -1., -1.),
+                Point3::new(-1., 1., -1.),
+                Point3::new(-1., -1., 1.),
+            ],
+            triangles: vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]],
+            ..MeshData::default()
+        }
+    }
+
+    #[test]
+    fn subdivides_closed_mesh() {
+        let mesh = loop_subdivide(&tetrahedron());
+        assert_eq!(mesh.positions.len(), 4 + 6);
+        assert_eq!(mesh.triangles.len(), 16);
+        assert_eq!(mesh.normals.len(), 10);
+        // With three neighbours the corners move to a quarter of their distance, and
+        // the other two corners of the edges cancel out the corners of each edge
+        assert!((mesh.positions[0] - Point3::new(0.25, 0.25, 0.25)).length() < 1e-12);
+        let edge = mesh.positions[4];
+        assert!((edge - Point3::new(0.5, 0., 0.)).length() < 1e-12);
+        // The normals still point outward
+        assert!(mesh.normals[0].dot(&mesh.positions[0]) > 0.);
+    }
+
+    #[test]
+    fn keeps_boundaries_and_seams_connected() {
+        // Two triangles of a square that do not share their vertices
+        let mesh = MeshData {
+            positions: vec![
+                Point3::new(0., 0., 0.),
+                Point3::new(1., 0., 0.),
+                Point3::new(1., 1., 0.),
+                Point3::new(0., 0., 0.),
+                Point3::new(1., 1., 0.),
+                Point3::new(0., 1., 0.),
+            ],
+            uvs: vec![(0., 0.), (1., 0.), (1., 1.), (0., 0.), (1., 1.), (0., 1.)],
+            triangles: vec![[0, 1, 2], [3, 4, 5]],
+            ..MeshData::default()
+        };
+        let refined = loop_subdivide(&mesh);
+        // Each side gets its own midpoint on the diagonal, at the same position
+        assert_eq!(refined.positions.len(), 6 + 6);
+        let diagonal: Vec<&Point3> = refined
+            .positions
+            .iter()
+            .filter(|p| (p.x - 0.5).abs() < 1e-12 && (p.y - 0.5).abs() < 1e-12)
+            .collect();
+        assert_eq!(diagonal.len(), 2);
+        // The square stays flat and its corner keeps its boundary neighbours in mind
+        assert!(refined.positions.iter().all(|p| p.z == 0.));
+        assert!((refined.positions[1] - Point3::new(0.875, 0.125, 0.)).length() < 1e-12);
+        assert_eq!(refined.uvs.len(), refined.positions.len());
+    }
+
+    #[test]
+    fn displaces_along_normals() {
+        let mesh = loop_subdivide(&tetrahedron());
+        let gray = SolidColor::new(Color::new(0.5, 0.5, 0.5));
+        let displaced = displace(&mesh, &gray, 0.2);
+        for (before, after) in mesh.positions.iter().zip(&displaced.positions) {
+            let offset = after - before;
+            assert!((offset.length() - 0.1).abs() < 1e-12);
+        }
+    }
+}
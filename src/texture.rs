@@ -0,0 +1,148 @@
+use crate::color::Color;
+use crate::load::{self, LoadError};
+use crate::vec3::Point3;
+use std::path::Path;
+
+/// Color that varies over a surface
+pub trait Texture: Send + Sync {
+    /// Color at the texture coordinates (u, v) of the point p
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+/// A texture with the same color everywhere
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color
+    }
+}
+
+/// A texture from an image with linear colors
+///
+/// The image repeats outside the [0, 1] range and is filtered bilinearly. The texture
+/// coordinate v = 0 is at the bottom of the image
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Pixels stored row by row, starting at the top left
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    /// Create a texture from the pixels of an image, stored row by row starting at the
+    /// top left
+    ///
+    /// Panics when the number of pixels does not match the size
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "texture needs at least one pixel");
+        assert_eq!(pixels.len(), width * height, "texture size mismatch");
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Load a PNG image with the values as they are stored, without decoding sRGB
+    ///
+    /// Meant for data like displacement maps, grayscale images give gray colors
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let (width, height, pixels) =
+            decode_png(&load::read_file(path)?).map_err(|message| LoadError {
+                path: Some(path.to_path_buf()),
+                message,
+            })?;
+        Ok(Self::new(width, height, pixels))
+    }
+
+    fn pixel(&self, x: isize, y: isize) -> Color {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        if !u.is_finite() || !v.is_finite() {
+            return self.pixels[0];
+        }
+        // Pixel centers are at half integer coordinates
+        let x = u * self.width as f64 - 0.5;
+        let y = (1. - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let top = (1. - fx) * self.pixel(x0, y0) + fx * self.pixel(x0 + 1, y0);
+        let bottom = (1. - fx) * self.pixel(x0, y0 + 1) + fx * self.pixel(x0 + 1, y0 + 1);
+        (1. - fy) * top + fy * bottom
+    }
+}
+
+/// Decode a PNG image to its size and pixels with values between 0 and 1, stored row
+/// by row starting at the top left
+pub(crate) fn decode_png(data: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    if info.width == 0 || info.height == 0 {
+        return Err("image is empty".into());
+    }
+
+    let channels = info.color_type.samples();
+    let size = match info.bit_depth {
+        png::BitDepth::Sixteen => 2,
+        _ => 1,
+    };
+    let value = |bytes: &[u8]| match size {
+        2 => u16::from_be_bytes([bytes[0], bytes[1]]) as f64 / u16::MAX as f64,
+        _ => bytes[0] as f64 / u8::MAX as f64,
+    };
+    let pixels = buffer[..info.buffer_size()]
+        .chunks_exact(channels * size)
+        .map(|pixel| {
+            let channel = |i: usize| value(&pixel[i * size..]);
+            if channels < 3 {
+                // Grayscale, possibly with alpha
+                Color::new(channel(0), channel(0), channel(0))
+            } else {
+                Color::new(channel(0), channel(1), channel(2))
+            }
+        })
+        .collect();
+    Ok((info.width as usize, info.height as usize, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_png() {
+        let mut data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut data, 2, 1);
+            encoder.set_color(png::ColorType::Grayscale);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0, 255]).unwrap();
+        }
+        let (width, height, pixels) = decode_png(&data).unwrap();
+        assert_eq!((width, height), (2, 1));
+        let values: Vec<f64> = pixels.iter().map(|pixel| pixel.y).collect();
+        assert_eq!(values, vec![0., 1.]);
+        assert!(decode_png(b"not an image").is_err());
+    }
+}
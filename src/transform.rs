@@ -0,0 +1,148 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, Solid};
+use crate::matrix::Mat4;
+use crate::ray::Ray;
+use crate::vec3::Point3;
+use std::sync::Arc;
+
+/// An object moved, rotated or scaled by an affine transform
+///
+/// Rays are transformed into the space of the object and the hits back out again. The
+/// ray direction is not normalized, so the t of a hit is the same in both spaces
+pub struct Transformed<H> {
+    object: H,
+    /// Transforms from object space to world space
+    transform: Mat4,
+    /// Transforms from world space to object space
+    inverse: Mat4,
+    bbox: Option<Aabb>,
+}
+
+/// An instance of shared geometry
+///
+/// The same object, e.g. a large mesh, can be placed many times while its data is
+/// only stored once
+pub type Instance = Transformed<Arc<dyn Hittable>>;
+
+impl<H: Hittable> Transformed<H> {
+    /// Place the object with a transform from object space to world space
+    ///
+    /// Panics when the transform can not be inverted
+    pub fn new(object: H, transform: Mat4) -> Self {
+        let inverse = transform
+            .inverse()
+            .expect("transform of an object must be invertible");
+        // The box around the transformed corners of the object box contains the object
+        let bbox = object.bounding_box().map(|bbox| {
+            (0..8).fold(Aabb::empty(), |result, corner| {
+                let pick = |bit: usize, axis: usize| {
+                    if corner & (1 << bit) == 0 {
+                        bbox.min[axis]
+                    } else {
+                        bbox.max[axis]
+                    }
+                };
+                let p = Point3::new(pick(0, 0), pick(1, 1), pick(2, 2));
+                result.include(&transform.transform_point(&p))
+            })
+        });
+        Self {
+            object,
+            transform,
+            inverse,
+            bbox,
+        }
+    }
+
+    /// The transform from object space to world space
+    pub fn transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    fn to_local(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point(&r.origin),
+            self.inverse.transform_vector(&r.dir),
+        )
+    }
+
+    fn to_world<'a>(&self, mut hit: HitRecord<'a>) -> HitRecord<'a> {
+        // The normal already faces against the local ray, which the transform preserves
+        hit.p = self.transform.transform_point(&hit.p);
+        hit.normal = self.inverse.transform_normal(&hit.normal).unit_vector();
+        hit
+    }
+}
+
+impl<H: Hittable> Hittable for Transformed<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hit = self.object.hit(&self.to_local(r), t_min, t_max)?;
+        Some(self.to_world(hit))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
+
+impl<S: Solid> Solid for Transformed<S> {
+    fn intersections(&self, r: &Ray) -> Vec<HitRecord<'_>> {
+        let hits = self.object.intersections(&self.to_local(r));
+        hits.into_iter().map(|hit| self.to_world(hit)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Sphere;
+    use crate::material::tests::gray;
+    use crate::vec3::Vec3;
+
+    fn unit_sphere() -> Sphere {
+        let material = gray();
+        Sphere::new(Point3::zero(), 1., material)
+    }
+
+    #[test]
+    fn matches_moved_sphere() {
+        let offset = Vec3::new(1., 2., -3.);
+        let moved = Transformed::new(unit_sphere(), Mat4::translation(&offset));
+        let ray = Ray::new(Point3::new(1., 2., 5.), Vec3::new(0., 0., -2.));
+        let hit = moved.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 3.5).abs() < 1e-12);
+        assert!((hit.p - Point3::new(1., 2., -2.)).length() < 1e-12);
+        assert!((hit.normal - Vec3::new(0., 0., 1.)).length() < 1e-12);
+
+        let bbox = moved.bounding_box().unwrap();
+        assert!((bbox.min - Point3::new(0., 1., -4.)).length() < 1e-12);
+    }
+
+    #[test]
+    fn scaled_normals_stay_perpendicular() {
+        // An ellipsoid that is twice as wide as it is high
+        let squashed = Transformed::new(unit_sphere(), Mat4::scaling(&Vec3::new(2., 1., 1.)));
+        let ray = Ray::new(Point3::new(5., 0.5, 0.), Vec3::new(-1., 0., 0.));
+        let hit = squashed.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        // On the ellipse (x / 2)^2 + y^2 = 1 the normal is along (x / 4, y)
+        let expected = Vec3::new(hit.p.x / 4., hit.p.y, 0.).unit_vector();
+        assert!((hit.normal - expected).length() < 1e-12);
+        assert!(hit.front_face);
+    }
+
+    #[test]
+    fn instances_share_geometry() {
+        let shared: Arc<dyn Hittable> = Arc::new(unit_sphere());
+        let instances: Vec<Instance> = (0..3)
+            .map(|i| {
+                let offset = Vec3::new(3. * i as f64, 0., 0.);
+                Transformed::new(shared.clone(), Mat4::translation(&offset))
+            })
+            .collect();
+        assert_eq!(Arc::strong_count(&shared), 4);
+
+        let ray = Ray::new(Point3::new(6., 0., 5.), Vec3::new(0., 0., -1.));
+        assert!(instances[2].hit(&ray, 0.001, f64::INFINITY).is_some());
+        assert!(instances[1].hit(&ray, 0.001, f64::INFINITY).is_none());
+    }
+}
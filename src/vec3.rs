@@ -1,3 +1,4 @@
+use crate::sampler::Sampler;
 use rand::Rng;
 use std::ops;
 use std::ops::Neg;
@@ -32,27 +33,25 @@ impl Vec3 {
 
     /// Dot product
     pub fn dot(&self, b: &Vec3) -> f64 {
-        dot(&self, b)
+        dot(self, b)
     }
 
     /// Cross product
     pub fn cross(&self, b: &Vec3) -> Self {
-        cross(self, &b)
+        cross(self, b)
     }
 
     /// Generates a random vector between [0,1]
-    pub fn random() -> Self {
-        let mut rnd = rand::thread_rng();
-        Self::new(rnd.gen(), rnd.gen(), rnd.gen())
+    pub fn random(sampler: &mut Sampler) -> Self {
+        Self::new(sampler.gen(), sampler.gen(), sampler.gen())
     }
 
     /// Generates a random vector between the range [min, max]
-    pub fn random_range(min: f64, max: f64) -> Self {
-        let mut rnd = rand::thread_rng();
+    pub fn random_range(min: f64, max: f64, sampler: &mut Sampler) -> Self {
         Self::new(
-            rnd.gen_range(min, max),
-            rnd.gen_range(min, max),
-            rnd.gen_range(min, max),
+            sampler.gen_range(min, max),
+            sampler.gen_range(min, max),
+            sampler.gen_range(min, max),
         )
     }
 
@@ -62,10 +61,10 @@ impl Vec3 {
     }
 
     /// Return a random vector in the unit sphere
-    pub fn random_in_unit_sphere() -> Self {
+    pub fn random_in_unit_sphere(sampler: &mut Sampler) -> Self {
         // Just loop till you find one
         loop {
-            let p = Self::random();
+            let p = Self::random(sampler);
             if p.length_squared() < 1. {
                 return p;
             }
@@ -73,14 +72,23 @@ impl Vec3 {
     }
 
     /// Random unit vector according to lambertian distribution
-    pub fn random_unit_vector() -> Self {
-        let mut rnd = rand::thread_rng();
-        let a = rnd.gen_range(0., 2. * std::f64::consts::PI);
-        let z = rnd.gen_range(-1., 1.);
-        let r = (1. as f64 - z * z).sqrt();
+    pub fn random_unit_vector(sampler: &mut Sampler) -> Self {
+        let a = sampler.gen_range(0., 2. * std::f64::consts::PI);
+        let z: f64 = sampler.gen_range(-1., 1.);
+        let r = (1. - z * z).sqrt();
         Vec3::new(r * a.cos(), r * a.sin(), z)
     }
 
+    /// Component-wise minimum of two vectors
+    pub fn min(&self, b: &Vec3) -> Self {
+        Vec3::new(self.x.min(b.x), self.y.min(b.y), self.z.min(b.z))
+    }
+
+    /// Component-wise maximum of two vectors
+    pub fn max(&self, b: &Vec3) -> Self {
+        Vec3::new(self.x.max(b.x), self.y.max(b.y), self.z.max(b.z))
+    }
+
     /// Reflect the vector with regards to the normal n
     pub fn reflect(&self, n: &Vec3) -> Self {
         *self - 2. * self.dot(n) * (*n)
@@ -96,10 +104,13 @@ impl Vec3 {
     }
 
     /// Get a random vector in the unit disk
-    pub fn random_in_unit_disk() -> Self {
-        let mut rnd = rand::thread_rng();
+    pub fn random_in_unit_disk(sampler: &mut Sampler) -> Self {
         loop {
-            let p = Vec3::new(rnd.gen_range(-1.0, 1.0), rnd.gen_range(-1.0, 1.0), 0.);
+            let p = Vec3::new(
+                sampler.gen_range(-1.0, 1.0),
+                sampler.gen_range(-1.0, 1.0),
+                0.,
+            );
             if p.length_squared() < 1.0 {
                 return p;
             }
@@ -107,6 +118,12 @@ impl Vec3 {
     }
 }
 
+impl From<[f64; 3]> for Vec3 {
+    fn from(v: [f64; 3]) -> Self {
+        Vec3::new(v[0], v[1], v[2])
+    }
+}
+
 /// Calculates the dot product for the Vec3
 pub fn dot(a: &Vec3, b: &Vec3) -> f64 {
     a.x * b.x + a.y * b.y + a.z * b.z
@@ -224,6 +241,18 @@ impl ops::Div<f64> for Vec3 {
     }
 }
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("axis {} is out of range for a Vec3", axis),
+        }
+    }
+}
+
 impl Neg for Vec3 {
     type Output = Vec3;
 